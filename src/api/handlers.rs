@@ -1,16 +1,55 @@
-use super::interfaces::{ChunkMetadataPayload, DownloadParamsPayload, SigningDataPayload};
+use super::interfaces::{
+    AttachedSignPayload, AttachedVerifyPayload, BatchSignItem, BatchVerifyItem, BurnSecretPayload,
+    ChunkMetadataPayload, ConsistencyProofQuery, DataEncoding, DownloadParamsPayload,
+    FileSignPayload, FileSignatureQuery, FileVerifyPayload, InclusionProofQuery, JwtHeader,
+    JwtIssuePayload, JwtVerifyPayload, KeyCreatePayload, KeyImportPayload, LegalHoldPayload,
+    MasterSeedPayload, PolicyPayload, PolicyVerifyPayload, PublicKeyQuery, RawSignQuery,
+    SharePayload, ShareToken, SigningDataPayload, SshsigSignPayload, SshsigVerifyPayload,
+    TimestampPayload, TimestampVerifyPayload,
+};
+use super::utils::{
+    chunk_nonce, combine_passphrase, decode_jwt, decode_share_token, decrypted_digest,
+    decrypted_stream, encode_jwt_segment, encode_share_claims, frame_sealed_chunk,
+    read_sealed_chunk, unix_timestamp, JwtClaims,
+};
+use crate::crypto::generate_random;
+use crate::crypto::key_format::{
+    decode_pkcs8_private_key, export_public_key, keypair_from_any_pkcs8, parse_public_key,
+    public_key_to_jwk, public_key_to_openssh, PublicKeyFormat,
+};
+use crate::crypto::merkle;
 use crate::crypto::secretbox_chacha20_poly1305::{open, seal, Key, Nonce};
-use crate::crypto::sign_ed25519::Signature;
-use crate::db::secret_db::SecretDb;
-use crate::db::sign_db::SignatureDb;
+use crate::crypto::sha3_256;
+use crate::crypto::sign_ecdsa::SignatureEncoding;
+use crate::crypto::sign_ed25519::ED25519_SIGNATURE_LEN;
+use crate::crypto::sign_rsa::{RSA_DEFAULT_KEY_BITS, RSA_KEY_BITS};
+use crate::crypto::signing::{self, SigningAlgorithm};
+use crate::crypto::slip10::{self, MAX_SEED_LEN, MIN_SEED_LEN};
+use crate::crypto::sshsig::{self, Sshsig, SshsigHash};
+use crate::db::constants::{
+    INLINE_THRESHOLD, JWKS_GRACE_PERIOD, JWKS_MAX_AGE, LOG_KEY_ID, MAX_BATCH_ITEMS,
+    MAX_KEY_LIFETIME, SHARE_KEY_ID, SIG_TTL, TIMESTAMP_KEY_ID, TIMESTAMP_TOKEN_TYPE,
+};
+use crate::db::log_db::{tree_head_input, LogDb, LogEntry};
+use crate::db::secret_db::{BurnRead, CiphertextFormat, FileSignature, SecretDb, SecretEntry};
+use crate::db::sign_db::{
+    is_reserved_key_id, KeyLifetime, PolicySigner, SignatureDb, SignatureEntry, VerificationPolicy,
+};
 use crate::db::DbError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use futures::lock::Mutex;
 use serde_json::json;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use warp::http::StatusCode;
 use warp::{Rejection, Reply};
 
+const RESERVED_KEY_ERROR: &str = "This key id is reserved for the server";
+
 /// Responds to a 'ping' request with a 'pong' response
 ///
 pub async fn handle_ping() -> Result<impl Reply, Rejection> {
@@ -28,33 +67,410 @@ pub async fn handle_upload_raw(
     chunk: bytes::Bytes,
     secret_db: Arc<Mutex<SecretDb>>,
     passphrase: String,
-) -> Result<impl Reply, Rejection> {
-    let key = Key::new();
-    let nonce = Nonce::new();
+) -> Result<warp::reply::Response, Rejection> {
+    let ttl_expires_at = match metadata.ttl {
+        Some(ttl) => match unix_timestamp().checked_add(ttl) {
+            Some(expires_at) => Some(expires_at),
+            None => return Ok(error_reply("ttl is too large", StatusCode::BAD_REQUEST)),
+        },
+        None => None,
+    };
     let sec_db_lock = secret_db.lock().await;
-    let encrypted_data = seal(chunk.to_vec(), &nonce, &key).unwrap();
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(metadata.file_name.clone())
-        .expect("Failed to open or create file");
+    let previous = match sec_db_lock.get_secret_entry(&metadata.file_name).await {
+        Ok(Some(entry)) if entry.legal_hold => {
+            return Ok(error_reply("File is under legal hold", StatusCode::LOCKED));
+        }
+        Ok(previous) => previous,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    // A file is sealed with the key and nonce made for its first chunk, so later
+    // chunks are appended under those and a first chunk starts the file afresh
+    let appending = metadata.chunk_number > 0
+        && previous
+            .as_ref()
+            .is_some_and(|p| !p.inline && p.format == CiphertextFormat::Framed);
+    let (key, nonce, offset, compression) = match &previous {
+        Some(previous) if appending => {
+            if metadata
+                .compression
+                .is_some_and(|compression| compression != previous.compression)
+            {
+                return Ok(error_reply(
+                    "Compression cannot change partway through a file",
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+
+            let sec_entry = match sec_db_lock
+                .get_secret(&metadata.file_name, &passphrase)
+                .await
+            {
+                Ok(entry) => entry,
+                Err(e) => return Err(warp::reject::custom(e)),
+            };
+            let offset = match std::fs::metadata(&metadata.file_name) {
+                Ok(file_metadata) => file_metadata.len(),
+                Err(_) => {
+                    return Err(warp::reject::custom(DbError {
+                        message: "Failed to find encrypted file".to_string(),
+                    }));
+                }
+            };
+            (sec_entry.key, sec_entry.nonce, offset, previous.compression)
+        }
+        Some(previous) => {
+            if let Err(e) = sec_db_lock
+                .remove_ciphertext(&metadata.file_name, previous.inline)
+                .await
+            {
+                return Err(warp::reject::custom(e));
+            }
+            let compression = metadata.compression.unwrap_or_default();
+            (Key::new(), Nonce::new(), 0, compression)
+        }
+        None => {
+            let compression = metadata.compression.unwrap_or_default();
+            (Key::new(), Nonce::new(), 0, compression)
+        }
+    };
+
+    let expires_at = metadata
+        .expires_at
+        .or(ttl_expires_at)
+        .or(previous.as_ref().and_then(|previous| previous.expires_at));
+    let compressed_data = match compression.compress(chunk.to_vec()) {
+        Some(data) => data,
+        None => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to compress chunk".to_string(),
+            }));
+        }
+    };
+    let encrypted_data = frame_sealed_chunk(
+        seal(
+            compressed_data,
+            &chunk_nonce(&nonce, offset, CiphertextFormat::Framed),
+            &key,
+        )
+        .unwrap(),
+    );
+
+    // Small single-chunk uploads live in the database rather than in their own file
+    let inline =
+        !appending && metadata.total_chunks == 1 && encrypted_data.len() <= INLINE_THRESHOLD;
 
-    file.write_all(&encrypted_data)
-        .expect("Failed to write to file");
+    if inline {
+        if let Err(e) = sec_db_lock
+            .insert_inline_object(&metadata.file_name, encrypted_data)
+            .await
+        {
+            return Err(warp::reject::custom(e));
+        }
+    } else {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(metadata.file_name.clone())
+            .expect("Failed to open or create file");
+
+        file.write_all(&encrypted_data)
+            .expect("Failed to write to file");
+    }
 
-    // Save secret entry to DB
-    let sec_entry = sec_db_lock.create_secret_entry(
+    // Save secret entry to DB, keeping any signature so that verifying it reports
+    // whether the file has changed since
+    let mut sec_entry = sec_db_lock.create_secret_entry(
         &metadata.file_name,
         &passphrase,
         metadata.total_chunks,
         (key, nonce),
+        expires_at,
+        (compression, inline),
     );
+    sec_entry.signature = previous.and_then(|previous| previous.signature);
 
     match sec_db_lock.insert_secret(sec_entry).await {
         Ok(_) => Ok(warp::reply::with_status(
             "Chunk received and encrypted",
             warp::http::StatusCode::OK,
+        )
+        .into_response()),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Places or lifts a legal hold on a stored file
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file
+/// * `hold_payload` - Legal hold payload
+/// * `secret_db` - Secret database
+pub async fn handle_legal_hold(
+    file_name: String,
+    hold_payload: LegalHoldPayload,
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> Result<impl Reply, Rejection> {
+    match secret_db
+        .lock()
+        .await
+        .set_legal_hold(&file_name, hold_payload.legal_hold)
+        .await
+    {
+        Ok(_) => Ok(warp::reply::json(&json!({
+            "file_name": file_name,
+            "legal_hold": hold_payload.legal_hold
+        }))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Crypto-shreds a stored file, unless it is under legal hold
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file
+/// * `secret_db` - Secret database
+pub async fn handle_delete_file(
+    file_name: String,
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let sec_db_lock = secret_db.lock().await;
+    match sec_db_lock.get_secret_entry(&file_name).await {
+        Ok(Some(entry)) if entry.legal_hold => {
+            return Ok(error_reply("File is under legal hold", StatusCode::LOCKED));
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(error_reply("File not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+
+    match sec_db_lock.shred_secret(&file_name).await {
+        Ok(_) => Ok(warp::reply::json(&json!({ "deleted": file_name })).into_response()),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Signs the SHA3-256 digest of a stored file's plaintext with an existing, enabled
+/// keypair and stores the detached signature on the file entry. The signature is
+/// kept when the file is uploaded to again, and verifying then reports that the
+/// digest no longer matches
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file
+/// * `sign_payload` - File signing payload
+/// * `secret_db` - Secret database
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+pub async fn handle_file_sign(
+    file_name: String,
+    sign_payload: FileSignPayload,
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let format = match signature_format(sign_payload.signature_format) {
+        Some(format) => format,
+        None => {
+            return Ok(error_reply(
+                "Signatures cannot be encoded as UTF-8",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    if is_reserved_key_id(&sign_payload.key_id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let sec_db_lock = secret_db.lock().await;
+    let digest = match file_digest(&sec_db_lock, &file_name, &passphrase).await? {
+        Some(digest) => digest,
+        None => return Ok(error_reply("File not found", StatusCode::NOT_FOUND)),
+    };
+
+    let sign_result = signature_db
+        .lock()
+        .await
+        .sign_message(
+            &sign_payload.key_id,
+            &passphrase,
+            digest.clone(),
+            sign_payload.signature_encoding.unwrap_or_default(),
+            unix_timestamp(),
+        )
+        .await;
+    let signed = match sign_result {
+        Ok(Some(signed)) => signed,
+        Ok(None) => return unusable_key_reply(&signature_db, &sign_payload.key_id).await,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let entry = LogEntry::new(
+        &sign_payload.key_id,
+        signed.version,
+        signed.algorithm,
+        &signed.signature,
+        &digest,
+        unix_timestamp(),
+    );
+    let log_index = log_signatures(&log_db, vec![entry]).await?;
+
+    let file_signature = FileSignature {
+        key_id: sign_payload.key_id,
+        key_version: signed.version,
+        algorithm: signed.algorithm,
+        digest,
+        signature: signed.signature,
+        signed_at: unix_timestamp(),
+    };
+    let mut response = file_signature_json(&file_name, &file_signature, format);
+    response["log_index"] = json!(log_index[0]);
+
+    match sec_db_lock
+        .set_file_signature(&file_name, file_signature)
+        .await
+    {
+        Ok(_) => Ok(warp::reply::json(&response).into_response()),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Gets the stored signature of a file
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file
+/// * `query` - Signature output encoding
+/// * `secret_db` - Secret database
+pub async fn handle_file_signature(
+    file_name: String,
+    query: FileSignatureQuery,
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let format = match signature_format(query.signature_format) {
+        Some(format) => format,
+        None => {
+            return Ok(error_reply(
+                "Signatures cannot be encoded as UTF-8",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    match secret_db.lock().await.get_secret_entry(&file_name).await {
+        Ok(Some(entry)) if entry.is_expired(unix_timestamp()) => {
+            Ok(error_reply("File has expired", StatusCode::GONE))
+        }
+        Ok(Some(SecretEntry {
+            signature: Some(file_signature),
+            ..
+        })) => Ok(
+            warp::reply::json(&file_signature_json(&file_name, &file_signature, format))
+                .into_response(),
+        ),
+        Ok(Some(_)) => Ok(error_reply(
+            "File has not been signed",
+            StatusCode::NOT_FOUND,
+        )),
+        Ok(None) => Ok(error_reply("File not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Re-hashes a stored file and verifies a signature over its digest. The stored
+/// signature is used unless the payload supplies a key id and signature
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file
+/// * `verify_payload` - File verification payload
+/// * `secret_db` - Secret database
+/// * `signature_db` - Signature database
+pub async fn handle_file_verify(
+    file_name: String,
+    verify_payload: FileVerifyPayload,
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let sec_db_lock = secret_db.lock().await;
+    let stored = match sec_db_lock.get_secret_entry(&file_name).await {
+        Ok(Some(entry)) if entry.is_expired(unix_timestamp()) => {
+            return Ok(error_reply("File has expired", StatusCode::GONE))
+        }
+        Ok(Some(entry)) => entry.signature,
+        Ok(None) => return Ok(error_reply("File not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let (key_id, signature) = match (verify_payload.key_id, verify_payload.signature) {
+        (Some(key_id), Some(signature)) => {
+            let format = verify_payload.signature_format.unwrap_or(DataEncoding::Hex);
+            match format.decode(&signature) {
+                Some(signature) => (key_id, signature),
+                None => {
+                    return Ok(error_reply(
+                        "Failed to decode signature",
+                        StatusCode::BAD_REQUEST,
+                    ))
+                }
+            }
+        }
+        (None, None) => match &stored {
+            Some(file_signature) => (
+                file_signature.key_id.clone(),
+                file_signature.signature.clone(),
+            ),
+            None => {
+                return Ok(error_reply(
+                    "File has not been signed",
+                    StatusCode::NOT_FOUND,
+                ))
+            }
+        },
+        _ => {
+            return Ok(error_reply(
+                "Both key_id and signature are required",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let digest = match file_digest(&sec_db_lock, &file_name, &passphrase).await? {
+        Some(digest) => digest,
+        None => return Ok(error_reply("File not found", StatusCode::NOT_FOUND)),
+    };
+    drop(sec_db_lock);
+
+    let digest_matches = stored.map(|file_signature| file_signature.digest == digest);
+    let verify_result = signature_db
+        .lock()
+        .await
+        .verify_message(
+            &key_id,
+            &passphrase,
+            digest.clone(),
+            &signature,
+            unix_timestamp(),
+        )
+        .await;
+
+    match verify_result {
+        Ok(Some(valid)) => Ok(warp::reply::json(&json!({
+            "file_name": file_name,
+            "key_id": key_id,
+            "digest": hex::encode(&digest),
+            "digest_matches": digest_matches,
+            "valid": valid
+        }))
+        .into_response()),
+        Ok(None) => Ok(error_reply(
+            "No signing key exists for this id",
+            StatusCode::NOT_FOUND,
         )),
         Err(e) => Err(warp::reject::custom(e)),
     }
@@ -69,113 +485,2679 @@ pub async fn handle_download(
     params: DownloadParamsPayload,
     secret_db: Arc<Mutex<SecretDb>>,
     passphrase: String,
-) -> Result<impl Reply, Rejection> {
+) -> Result<warp::reply::Response, Rejection> {
     let sec_db_lock = secret_db.lock().await;
     let sec_entry = match sec_db_lock.get_secret(&params.file_name, &passphrase).await {
         Ok(entry) => entry,
         Err(e) => return Err(warp::reject::custom(e)),
     };
+    if sec_entry.is_expired(unix_timestamp()) {
+        return Ok(error_reply("File has expired", StatusCode::GONE));
+    }
 
-    let mut file = OpenOptions::new()
-        .read(true)
-        .open(params.file_name)
-        .expect("Failed to open file");
-
-    file.seek(SeekFrom::Start(params.offset))
-        .expect("Failed to seek in file");
+    let mut reader = match sec_db_lock
+        .open_ciphertext(&params.file_name, sec_entry.inline, params.offset)
+        .await
+    {
+        Ok(reader) => reader,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
 
-    let mut encrypted_chunk_window = vec![0; 2 * 1024 * 1024 + 16]; // 2MB + 16 bytes for GCM tag
-    let read_bytes = file
-        .read(&mut encrypted_chunk_window)
-        .expect("Failed to read from file");
-    let encrypted_chunk = &encrypted_chunk_window[0..read_bytes];
+    let (encrypted_chunk, stored_len) = match read_sealed_chunk(&mut reader, sec_entry.format) {
+        Ok(Some(read)) => read,
+        Ok(None) => {
+            return Ok(error_reply(
+                "Offset is past the end of the file",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+        Err(_) => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to read encrypted chunk".to_string(),
+            }))
+        }
+    };
+    let next_offset = params.offset + stored_len;
 
-    let decrypted_data = open(encrypted_chunk.to_vec(), &sec_entry.nonce, &sec_entry.key).unwrap();
-    let is_last_chunk = read_bytes < encrypted_chunk.len();
+    let nonce = chunk_nonce(&sec_entry.nonce, params.offset, sec_entry.format);
+    let decrypted_data = match open(encrypted_chunk, &nonce, &sec_entry.key)
+        .and_then(|data| sec_entry.compression.decompress(data))
+    {
+        Some(data) => data,
+        None => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt chunk".to_string(),
+            }))
+        }
+    };
+    let is_last_chunk = matches!(reader.read(&mut [0; 1]), Ok(0));
 
     let response = json!({
         "data": decrypted_data,
-        "isLastChunk": is_last_chunk
+        "isLastChunk": is_last_chunk,
+        "nextOffset": next_offset
     });
 
-    Ok(warp::reply::json(&response))
+    Ok(warp::reply::json(&response).into_response())
 }
 
-/// Signs a message with a newly generated public/private keypair
+/// Signs a message with an existing, enabled keypair. The message is decoded with
+/// `message_encoding` (UTF-8 by default) and the signature is returned in
+/// `signature_format` (hex by default)
 ///
 /// ### Arguments
 ///
 /// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
 /// * `message_payload` - Message payload
 pub async fn handle_sign(
     signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
     message_payload: SigningDataPayload,
     passphrase: String,
-) -> Result<impl Reply, Rejection> {
-    let id = message_payload.id.clone();
-    let sign_option = signature_db
-        .lock()
-        .await
-        .sign_message(&id, &passphrase, message_payload.message.into())
-        .await;
-    if let Some((signature, pub_key)) = sign_option {
-        let hex_sig = hex::encode(signature);
+) -> Result<warp::reply::Response, Rejection> {
+    let encoding = message_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&message_payload.message) {
+        Some(message) => message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
 
-        let response = json!({
-            "signature": hex_sig,
-            "public_key": pub_key,
-            "message_id": message_payload.id
-        });
+    sign_reply(
+        signature_db,
+        log_db,
+        message_payload.id,
+        message,
+        message_payload.signature_encoding.unwrap_or_default(),
+        message_payload.signature_format,
+        passphrase,
+    )
+    .await
+}
+
+/// Signs the raw bytes of an `application/octet-stream` body with an existing,
+/// enabled keypair
+///
+/// ### Arguments
+///
+/// * `query` - Key ID and signature encodings
+/// * `message` - Message to sign
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+pub async fn handle_sign_raw(
+    query: RawSignQuery,
+    message: bytes::Bytes,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    sign_reply(
+        signature_db,
+        log_db,
+        query.id,
+        message.to_vec(),
+        query.signature_encoding.unwrap_or_default(),
+        query.signature_format,
+        passphrase,
+    )
+    .await
+}
+
+/// Signs a message with an existing, enabled Ed25519 keypair and returns it with the
+/// signature attached. The signed message is returned in `signature_format` (hex by
+/// default)
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+/// * `attached_payload` - Message payload
+pub async fn handle_sign_attached(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    attached_payload: AttachedSignPayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let encoding = attached_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&attached_payload.message) {
+        Some(message) => message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let format = match signature_format(attached_payload.signature_format) {
+        Some(format) => format,
+        None => {
+            return Ok(error_reply(
+                "Signed messages cannot be encoded as UTF-8",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
 
-        return Ok(warp::reply::json(&response));
+    let id = attached_payload.id;
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+    let sig_db_lock = signature_db.lock().await;
+    if let Some(reply) = attached_key_check(&sig_db_lock, &id, true).await? {
+        return Ok(reply);
     }
 
-    Err(warp::reject::custom(DbError {
-        message: "Failed to sign message".to_string(),
-    }))
+    match sig_db_lock
+        .sign_message_attached(&id, &passphrase, message.clone(), unix_timestamp())
+        .await
+    {
+        Ok(Some((signed_message, version))) => {
+            let entry = LogEntry::new(
+                &id,
+                version,
+                SigningAlgorithm::Ed25519,
+                &signed_message[message.len()..],
+                &message,
+                unix_timestamp(),
+            );
+            let log_index = log_signatures(&log_db, vec![entry]).await?;
+
+            let response = json!({
+                "signed_message": format.encode(&signed_message),
+                "key_version": version,
+                "message_id": id,
+                "log_index": log_index[0]
+            });
+
+            Ok(warp::reply::json(&response).into_response())
+        }
+        Ok(None) => Ok(error_reply(
+            "No enabled signing key exists for this id",
+            StatusCode::NOT_FOUND,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
 }
 
-/// Verifies a message with the provided signature
+/// Verifies a message with its Ed25519 signature attached. The message is only
+/// returned, in `message_encoding` (UTF-8 by default), when the signature is valid
 ///
-/// TODO: Add for case where signature data is not present in the DB, so allow for
-/// the payload to include a public key and verify with that as a default
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `attached_payload` - Signed message payload
+pub async fn handle_verify_attached(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    attached_payload: AttachedVerifyPayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let signed_message = match signature_format(attached_payload.signature_format)
+        .and_then(|format| format.decode(&attached_payload.signed_message))
+    {
+        Some(signed_message) => signed_message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode signed message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let id = attached_payload.id;
+    let sig_db_lock = signature_db.lock().await;
+    if let Some(reply) = attached_key_check(&sig_db_lock, &id, false).await? {
+        return Ok(reply);
+    }
+
+    let verification = match sig_db_lock
+        .verify_message_attached(&id, &passphrase, &signed_message, unix_timestamp())
+        .await
+    {
+        Ok(Some(verification)) => verification,
+        Ok(None) => {
+            return Ok(error_reply(
+                "No signing key exists for this id",
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    if !verification {
+        return Ok(warp::reply::json(&json!({
+            "verification": false,
+            "message_id": id
+        }))
+        .into_response());
+    }
+
+    let message = &signed_message[..signed_message.len() - ED25519_SIGNATURE_LEN];
+    let encoding = attached_payload.message_encoding.unwrap_or_default();
+    match encoding.encode(message) {
+        Some(message) => Ok(warp::reply::json(&json!({
+            "verification": true,
+            "message": message,
+            "message_id": id
+        }))
+        .into_response()),
+        None => Ok(error_reply(
+            "The signed message is not valid UTF-8, request another message_encoding",
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )),
+    }
+}
+
+/// Signs a message with an existing, enabled Ed25519 keypair as an armored SSH
+/// signature, in the format of `ssh-keygen -Y sign` with a SHA-512 message hash
 ///
 /// ### Arguments
 ///
 /// * `signature_db` - Signature database
-/// * `message_payload` - Message payload
-pub async fn handle_verify(
+/// * `log_db` - Transparency log database
+/// * `sshsig_payload` - Message and namespace to sign
+pub async fn handle_sign_sshsig(
     signature_db: Arc<Mutex<SignatureDb>>,
-    message_payload: SigningDataPayload,
+    log_db: Arc<Mutex<LogDb>>,
+    sshsig_payload: SshsigSignPayload,
     passphrase: String,
-) -> Result<impl Reply, Rejection> {
-    let id = message_payload.id.clone();
-    let sig = match message_payload.signature {
-        Some(sig) => match Signature::from_slice(&hex::decode(sig).unwrap()) {
-            Some(sig) => sig,
-            None => {
-                return Err(warp::reject::custom(DbError {
-                    message: "Failed to decode signature".to_string(),
-                }));
-            }
-        },
+) -> Result<warp::reply::Response, Rejection> {
+    let encoding = sshsig_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&sshsig_payload.message) {
+        Some(message) => message,
         None => {
-            return Err(warp::reject::custom(DbError {
-                message: "Signature not provided".to_string(),
-            }));
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
         }
     };
+    if sshsig_payload.namespace.is_empty() {
+        return Ok(error_reply(
+            "SSH signatures need a namespace",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
 
-    let verification = signature_db
-        .lock()
+    let id = sshsig_payload.id;
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+    let sig_db_lock = signature_db.lock().await;
+    match sig_db_lock.get_signature_data(id.clone()).await {
+        Ok(Some(sig_data)) if sig_data.algorithm != SigningAlgorithm::Ed25519 => {
+            return Ok(error_reply(
+                "SSH signatures need an Ed25519 signing key",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+        Ok(Some(sig_data)) if !sig_data.disabled && sig_data.is_expired(unix_timestamp()) => {
+            return Ok(error_reply(
+                "Signing key has expired",
+                StatusCode::FORBIDDEN,
+            ))
+        }
+        Ok(_) => {}
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+
+    let hash = SshsigHash::Sha512;
+    let signed_data = sshsig::signed_data(&sshsig_payload.namespace, hash, &message);
+    let signed = match sig_db_lock
+        .sign_message(
+            &id,
+            &passphrase,
+            signed_data.clone(),
+            SignatureEncoding::default(),
+            unix_timestamp(),
+        )
         .await
-        .verify_message(&id, &passphrase, message_payload.message.into(), sig)
-        .await;
+    {
+        Ok(Some(signed)) if signed.algorithm == SigningAlgorithm::Ed25519 => signed,
+        Ok(_) => {
+            return Ok(error_reply(
+                "No enabled signing key exists for this id",
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    drop(sig_db_lock);
+
+    let sshsig = Sshsig {
+        public_key: signed.public_key,
+        namespace: sshsig_payload.namespace,
+        hash,
+        signature: signed.signature,
+    };
+    let (armored, public_key) = match (
+        sshsig.to_armored(),
+        public_key_to_openssh(signed.algorithm, &sshsig.public_key, &id),
+    ) {
+        (Some(armored), Some(public_key)) => (armored, public_key),
+        _ => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to encode SSH signature".to_string(),
+            }))
+        }
+    };
+
+    let entry = LogEntry::new(
+        &id,
+        signed.version,
+        signed.algorithm,
+        &sshsig.signature,
+        &signed_data,
+        unix_timestamp(),
+    );
+    let log_index = log_signatures(&log_db, vec![entry]).await?;
+
+    let response = json!({
+        "signature": armored,
+        "namespace": sshsig.namespace,
+        "public_key": public_key,
+        "fingerprint": sshsig.fingerprint(),
+        "key_version": signed.version,
+        "message_id": id,
+        "log_index": log_index[0]
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Verifies an armored SSH signature against an allowed_signers list, as
+/// `ssh-keygen -Y verify` does. Without a principal, the principals of every entry
+/// that allows the signing key are reported, as `ssh-keygen -Y find-principals` does
+///
+/// ### Arguments
+///
+/// * `verify_payload` - Signature, message and allowed signers
+pub async fn handle_verify_sshsig(
+    verify_payload: SshsigVerifyPayload,
+) -> Result<warp::reply::Response, Rejection> {
+    let encoding = verify_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&verify_payload.message) {
+        Some(message) => message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let sshsig = match Sshsig::from_armored(&verify_payload.signature) {
+        Some(sshsig) => sshsig,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode an Ed25519 SSH signature",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let allowed_signers = match sshsig::parse_allowed_signers(&verify_payload.allowed_signers) {
+        Ok(allowed_signers) => allowed_signers,
+        Err(line) => {
+            return Ok(error_reply(
+                &format!("Failed to parse line {} of allowed_signers", line),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let now = unix_timestamp();
+    let principals: Vec<&str> = allowed_signers
+        .iter()
+        .filter(|signer| signer.allows(&sshsig.public_key, &sshsig.namespace, now))
+        .filter_map(|signer| match &verify_payload.principal {
+            Some(principal) => signer
+                .matches_principal(principal)
+                .then_some(principal.as_str()),
+            None => Some(signer.principals.as_str()),
+        })
+        .collect();
+    let verification = sshsig.namespace == verify_payload.namespace
+        && !principals.is_empty()
+        && sshsig.verify(&message);
 
     let response = json!({
         "verification": verification,
-        "message_id": message_payload.id
+        "principals": if verification { principals } else { Vec::new() },
+        "namespace": sshsig.namespace,
+        "fingerprint": sshsig.fingerprint()
     });
 
-    Ok(warp::reply::json(&response))
-}
\ No newline at end of file
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Signs a batch of messages. Each distinct ID's keypair is decrypted once, and a
+/// failure for one message is reported in its result instead of failing the batch
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+/// * `items` - Messages to sign
+pub async fn handle_sign_batch(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    items: Vec<BatchSignItem>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if items.len() > MAX_BATCH_ITEMS {
+        return Ok(error_reply(
+            &format!("A batch can hold at most {} items", MAX_BATCH_ITEMS),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+
+    // Items that cannot be decoded are answered without touching the database
+    let mut results: Vec<Option<serde_json::Value>> = Vec::with_capacity(items.len());
+    let mut outputs = Vec::new();
+    let mut messages = Vec::new();
+    for item in items {
+        if is_reserved_key_id(&item.id) {
+            results.push(Some(json!({
+                "message_id": item.id,
+                "error": RESERVED_KEY_ERROR
+            })));
+            continue;
+        }
+        let encoding = item.message_encoding.unwrap_or_default();
+        match (
+            encoding.decode(&item.message),
+            signature_format(item.signature_format),
+        ) {
+            (Some(message), Some(format)) => {
+                results.push(None);
+                outputs.push((item.id.clone(), format));
+                let signature_encoding = item.signature_encoding.unwrap_or_default();
+                messages.push((item.id, message, signature_encoding));
+            }
+            (None, _) => results.push(Some(json!({
+                "message_id": item.id,
+                "error": "Failed to decode message"
+            }))),
+            (_, None) => results.push(Some(json!({
+                "message_id": item.id,
+                "error": "Signatures cannot be encoded as UTF-8"
+            }))),
+        }
+    }
+
+    let signed = signature_db
+        .lock()
+        .await
+        .sign_messages(&passphrase, messages.clone(), unix_timestamp())
+        .await;
+
+    // Every issued signature is logged before any of them is released
+    let now = unix_timestamp();
+    let entries = messages
+        .iter()
+        .zip(&signed)
+        .filter_map(|((id, message, _), signed)| match signed {
+            Ok(Some(signed)) => Some(LogEntry::new(
+                id,
+                signed.version,
+                signed.algorithm,
+                &signed.signature,
+                message,
+                now,
+            )),
+            _ => None,
+        })
+        .collect();
+    let mut log_indices = log_signatures(&log_db, entries).await?.into_iter();
+
+    let mut signed = outputs
+        .into_iter()
+        .zip(signed)
+        .map(|((id, format), signed)| match signed {
+            Ok(Some(signed)) => json!({
+                "message_id": id,
+                "signature": format.encode(&signed.signature),
+                "public_key": signed.public_key,
+                "key_version": signed.version,
+                "algorithm": signed.algorithm,
+                "log_index": log_indices.next()
+            }),
+            Ok(None) => json!({
+                "message_id": id,
+                "error": "No enabled, unexpired signing key exists for this id"
+            }),
+            Err(e) => json!({ "message_id": id, "error": e.message }),
+        });
+    let results: Vec<serde_json::Value> = results
+        .into_iter()
+        .map(|result| result.or_else(|| signed.next()).unwrap_or_default())
+        .collect();
+
+    Ok(warp::reply::json(&json!({ "results": results })).into_response())
+}
+
+/// Verifies a batch of signatures against the stored keypairs. Each distinct ID's
+/// public keys are decrypted once, and a failure for one signature is reported in
+/// its result instead of failing the batch
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `items` - Signatures to verify
+pub async fn handle_verify_batch(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    items: Vec<BatchVerifyItem>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if items.len() > MAX_BATCH_ITEMS {
+        return Ok(error_reply(
+            &format!("A batch can hold at most {} items", MAX_BATCH_ITEMS),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+
+    // Items that cannot be decoded are answered without touching the database
+    let mut results: Vec<Option<serde_json::Value>> = Vec::with_capacity(items.len());
+    let mut ids = Vec::new();
+    let mut messages = Vec::new();
+    for item in items {
+        let encoding = item.message_encoding.unwrap_or_default();
+        let signature = signature_format(item.signature_format)
+            .and_then(|format| format.decode(&item.signature));
+        match (encoding.decode(&item.message), signature) {
+            (Some(message), Some(signature)) => {
+                results.push(None);
+                ids.push(item.id.clone());
+                messages.push((item.id, message, signature));
+            }
+            (None, _) => results.push(Some(json!({
+                "message_id": item.id,
+                "error": "Failed to decode message"
+            }))),
+            (_, None) => results.push(Some(json!({
+                "message_id": item.id,
+                "error": "Failed to decode signature"
+            }))),
+        }
+    }
+
+    let verified = signature_db
+        .lock()
+        .await
+        .verify_messages(&passphrase, messages, unix_timestamp())
+        .await;
+    let mut verified = ids
+        .into_iter()
+        .zip(verified)
+        .map(|(id, verified)| match verified {
+            Ok(Some(verification)) => json!({ "message_id": id, "verification": verification }),
+            Ok(None) => json!({
+                "message_id": id,
+                "error": "No signing key exists for this id"
+            }),
+            Err(e) => json!({ "message_id": id, "error": e.message }),
+        });
+    let results: Vec<serde_json::Value> = results
+        .into_iter()
+        .map(|result| result.or_else(|| verified.next()).unwrap_or_default())
+        .collect();
+
+    Ok(warp::reply::json(&json!({ "results": results })).into_response())
+}
+
+/// Creates a new signing keypair for an ID. RSA keys are 2048 bits unless the
+/// payload asks for another size between 2048 and 4096. Derived keys are Ed25519
+/// keys derived from the master seed at the given SLIP-0010 path, or at a path
+/// taken from the ID
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `key_payload` - Key creation payload
+pub async fn handle_key_create(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    key_payload: KeyCreatePayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&key_payload.id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let algorithm = key_payload.algorithm.unwrap_or_default();
+    let rsa_bits = key_payload.key_size.unwrap_or(RSA_DEFAULT_KEY_BITS);
+    if algorithm.rsa_padding().is_some() && !RSA_KEY_BITS.contains(&rsa_bits) {
+        return Ok(error_reply(
+            "RSA key size must be between 2048 and 4096 bits",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let derived = key_payload.derived.unwrap_or(false) || key_payload.derivation_path.is_some();
+    if derived && algorithm != SigningAlgorithm::Ed25519 {
+        return Ok(error_reply(
+            "Only Ed25519 keys can be derived",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let path = match key_payload
+        .derivation_path
+        .as_deref()
+        .map(slip10::parse_path)
+    {
+        Some(Some(path)) => Some(path),
+        Some(None) => {
+            return Ok(error_reply(
+                "Derivation path must look like m/44'/0'/0', with only hardened indices",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+        None => None,
+    };
+    let lifetime = match key_lifetime(key_payload.ttl, key_payload.not_after) {
+        Some(lifetime) => lifetime,
+        None => {
+            return Ok(error_reply(
+                "not_after must be in the future and at most 100 years away",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let sig_db_lock = signature_db.lock().await;
+    let created = match derived {
+        true => {
+            sig_db_lock
+                .create_derived_key(&key_payload.id, &passphrase, path, lifetime)
+                .await
+        }
+        false => {
+            sig_db_lock
+                .create_key(&key_payload.id, &passphrase, algorithm, rsa_bits, lifetime)
+                .await
+        }
+    };
+    match created {
+        Ok(Some(sig_data)) => match key_metadata(&sig_db_lock, &sig_data, &passphrase) {
+            Some(metadata) => Ok(warp::reply::with_status(
+                warp::reply::json(&metadata),
+                StatusCode::CREATED,
+            )
+            .into_response()),
+            None => Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            })),
+        },
+        Ok(None) => Ok(error_reply(
+            "A signing key already exists for this id, or the derivation path is in use",
+            StatusCode::CONFLICT,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Imports an existing Ed25519, P-256, P-384 or RSA private key, given as PKCS8 DER or
+/// PEM and optionally password-encrypted, as the signing keypair of an ID. The payload
+/// can name the algorithm, which picks the padding of an RSA key
+///
+/// ### Arguments
+///
+/// * `id` - ID to import the keypair under
+/// * `import_payload` - Key import payload
+/// * `signature_db` - Signature database
+pub async fn handle_key_import(
+    id: String,
+    import_payload: KeyImportPayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let keypair = decode_pkcs8_private_key(
+        &import_payload.private_key,
+        import_payload.password.as_deref(),
+    )
+    .and_then(|der| keypair_from_any_pkcs8(&der, import_payload.algorithm));
+    let (algorithm, keypair) = match keypair {
+        Some(keypair) => keypair,
+        None => return Ok(error_reply(
+            "Failed to decode an Ed25519, P-256, P-384 or 2048 to 4096 bit RSA PKCS8 private key",
+            StatusCode::BAD_REQUEST,
+        )),
+    };
+    if import_payload.algorithm.is_some_and(|a| a != algorithm) {
+        return Ok(error_reply(
+            "The private key does not match the requested algorithm",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let lifetime = match key_lifetime(import_payload.ttl, import_payload.not_after) {
+        Some(lifetime) => lifetime,
+        None => {
+            return Ok(error_reply(
+                "not_after must be in the future and at most 100 years away",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let sig_db_lock = signature_db.lock().await;
+    match sig_db_lock
+        .import_key(&id, &passphrase, algorithm, keypair, lifetime)
+        .await
+    {
+        Ok(Some(sig_data)) => match key_metadata(&sig_db_lock, &sig_data, &passphrase) {
+            Some(metadata) => Ok(warp::reply::with_status(
+                warp::reply::json(&metadata),
+                StatusCode::CREATED,
+            )
+            .into_response()),
+            None => Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            })),
+        },
+        Ok(None) => Ok(error_reply(
+            "A signing key already exists for this id, or this key is already stored",
+            StatusCode::CONFLICT,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Rotates a signing keypair, adding a new version that is used for all new signatures
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `signature_db` - Signature database
+pub async fn handle_key_rotate(
+    id: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    match sig_db_lock
+        .rotate_key(&id, &passphrase, unix_timestamp())
+        .await
+    {
+        Ok(Some(sig_data)) => match key_metadata(&sig_db_lock, &sig_data, &passphrase) {
+            Some(metadata) => Ok(warp::reply::with_status(
+                warp::reply::json(&metadata),
+                StatusCode::CREATED,
+            )
+            .into_response()),
+            None => Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            })),
+        },
+        Ok(None) => Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Retires an older version of a signing keypair so it no longer verifies
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `version` - Version to retire
+/// * `signature_db` - Signature database
+pub async fn handle_key_retire(
+    id: String,
+    version: u32,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    match signature_db
+        .lock()
+        .await
+        .retire_key_version(&id, version, unix_timestamp())
+        .await
+    {
+        Ok(Some(true)) => Ok(warp::reply::json(&json!({
+            "id": id,
+            "version": version,
+            "retired": true
+        }))
+        .into_response()),
+        Ok(Some(false)) => Ok(error_reply(
+            "The newest key version cannot be retired",
+            StatusCode::CONFLICT,
+        )),
+        Ok(None) => Ok(error_reply("Key version not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Gets the metadata and public key of a signing keypair
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `signature_db` - Signature database
+pub async fn handle_key_get(
+    id: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let sig_db_lock = signature_db.lock().await;
+    match sig_db_lock.get_signature_data(id).await {
+        Ok(Some(sig_data)) => match key_metadata(&sig_db_lock, &sig_data, &passphrase) {
+            Some(metadata) => Ok(warp::reply::json(&metadata).into_response()),
+            None => Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            })),
+        },
+        Ok(None) => Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Creates a named verification policy requiring valid signatures from at least
+/// `threshold` of its Ed25519 signers. No two signers may share a key, counting every
+/// version of a stored keypair
+///
+/// ### Arguments
+///
+/// * `policy_payload` - Policy creation payload
+/// * `signature_db` - Signature database
+pub async fn handle_policy_create(
+    policy_payload: PolicyPayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let signer_count = policy_payload.signers.len();
+    if policy_payload.threshold == 0 || policy_payload.threshold > signer_count {
+        return Ok(error_reply(
+            "Threshold must be between 1 and the number of signers",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let mut names: Vec<&str> = policy_payload
+        .signers
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    if names.len() != signer_count || names.contains(&"") {
+        return Ok(error_reply(
+            "Signers need unique, non-empty names",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    let mut signers = Vec::with_capacity(signer_count);
+    let mut pk_hashes = Vec::with_capacity(signer_count);
+    for signer in policy_payload.signers {
+        let public_key = match (&signer.public_key, &signer.key_id) {
+            (Some(public_key), None) => match parse_public_key(public_key) {
+                Some((SigningAlgorithm::Ed25519, pub_key)) => Some(pub_key),
+                _ => {
+                    return Ok(error_reply(
+                        "Signer public keys must be Ed25519 public keys",
+                        StatusCode::BAD_REQUEST,
+                    ))
+                }
+            },
+            (None, Some(key_id)) => match sig_db_lock.get_signature_data(key_id.clone()).await {
+                Ok(Some(sig_data)) if sig_data.algorithm == SigningAlgorithm::Ed25519 => None,
+                Ok(Some(_)) => {
+                    return Ok(error_reply(
+                        "Signer keys must be Ed25519 keys",
+                        StatusCode::BAD_REQUEST,
+                    ))
+                }
+                Ok(None) => {
+                    return Ok(error_reply(
+                        "No signing key exists for a signer key_id",
+                        StatusCode::NOT_FOUND,
+                    ))
+                }
+                Err(e) => return Err(warp::reject::custom(e)),
+            },
+            _ => {
+                return Ok(error_reply(
+                    "Each signer needs exactly one of public_key and key_id",
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        };
+
+        let signer_pk_hashes = match (&public_key, &signer.key_id) {
+            (Some(pub_key), _) => vec![hex::encode(sha3_256::digest(pub_key))],
+            (None, Some(key_id)) => match sig_db_lock.get_key_versions(key_id.clone()).await {
+                Ok(versions) => versions
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|sig_data| sig_data.pk_hash)
+                    .collect(),
+                Err(e) => return Err(warp::reject::custom(e)),
+            },
+            (None, None) => Vec::new(),
+        };
+        if signer_pk_hashes
+            .iter()
+            .any(|pk_hash| pk_hashes.contains(pk_hash))
+        {
+            return Ok(error_reply(
+                "Signers must not share a key",
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+        pk_hashes.extend(signer_pk_hashes);
+
+        signers.push(PolicySigner {
+            name: signer.name,
+            key_id: public_key.is_none().then_some(signer.key_id).flatten(),
+            public_key: public_key.map(hex::encode),
+        });
+    }
+
+    let policy = VerificationPolicy {
+        name: policy_payload.name,
+        threshold: policy_payload.threshold,
+        signers,
+        created_at: unix_timestamp(),
+    };
+    match sig_db_lock.create_policy(&policy).await {
+        Ok(true) => Ok(
+            warp::reply::with_status(warp::reply::json(&policy), StatusCode::CREATED)
+                .into_response(),
+        ),
+        Ok(false) => Ok(error_reply(
+            "A policy with this name already exists",
+            StatusCode::CONFLICT,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Gets a verification policy
+///
+/// ### Arguments
+///
+/// * `name` - Name of the policy
+/// * `signature_db` - Signature database
+pub async fn handle_policy_get(
+    name: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    match signature_db.lock().await.get_policy(&name).await {
+        Ok(Some(policy)) => Ok(warp::reply::json(&policy).into_response()),
+        Ok(None) => Ok(error_reply("Policy not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Deletes a verification policy
+///
+/// ### Arguments
+///
+/// * `name` - Name of the policy
+/// * `signature_db` - Signature database
+pub async fn handle_policy_delete(
+    name: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    match signature_db.lock().await.delete_policy(&name).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(false) => Ok(error_reply("Policy not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Checks a set of Ed25519 signatures over a message against a verification policy,
+/// reporting which signers signed and whether the threshold is met
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `verify_payload` - Policy verification payload
+pub async fn handle_verify_policy(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    verify_payload: PolicyVerifyPayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let encoding = verify_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&verify_payload.message) {
+        Some(message) => message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let format = match signature_format(verify_payload.signature_format) {
+        Some(format) => format,
+        None => {
+            return Ok(error_reply(
+                "Signatures cannot be encoded as UTF-8",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    if verify_payload.signatures.len() > MAX_BATCH_ITEMS {
+        return Ok(error_reply(
+            &format!(
+                "A policy check can hold at most {} signatures",
+                MAX_BATCH_ITEMS
+            ),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+    let mut signatures = Vec::with_capacity(verify_payload.signatures.len());
+    for signature in verify_payload.signatures {
+        match format.decode(&signature.signature) {
+            Some(decoded) => signatures.push((signature.signer, decoded)),
+            None => {
+                return Ok(error_reply(
+                    "Failed to decode signature",
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        }
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    let policy = match sig_db_lock.get_policy(&verify_payload.policy).await {
+        Ok(Some(policy)) => policy,
+        Ok(None) => return Ok(error_reply("Policy not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let verification = match sig_db_lock
+        .verify_policy(&policy, &passphrase, &message, signatures, unix_timestamp())
+        .await
+    {
+        Ok(verification) => verification,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let response = json!({
+        "policy": policy.name,
+        "threshold": policy.threshold,
+        "satisfied": verification.satisfied,
+        "valid_signers": verification.valid_signers,
+        "invalid_signers": verification.invalid_signers,
+        "unknown_signers": verification.unknown_signers,
+        "invalid_signatures": verification.invalid_signatures
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Reproduces the public keys of a derived signing keypair from the master seed and
+/// checks them against the stored ones, so that a seed backup can be audited
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `signature_db` - Signature database
+pub async fn handle_key_derivation(
+    id: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let sig_db_lock = signature_db.lock().await;
+    let versions = match sig_db_lock.get_key_versions(id.clone()).await {
+        Ok(Some(versions)) if !versions.is_empty() => versions,
+        Ok(_) => return Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if versions.iter().all(|v| v.derivation_path.is_none()) {
+        return Ok(error_reply(
+            "Signing key was not derived from the master seed",
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let mut results = Vec::with_capacity(versions.len());
+    for sig_data in versions {
+        let path = match sig_data
+            .derivation_path
+            .as_deref()
+            .and_then(slip10::parse_path)
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        let derived = match sig_db_lock.derive_keypair(&passphrase, &path).await {
+            Ok(Some((pub_key, _))) => pub_key,
+            Ok(None) => {
+                return Err(warp::reject::custom(DbError {
+                    message: "Failed to derive signing key".to_string(),
+                }))
+            }
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+        let stored = sig_db_lock.get_public_key(&sig_data, &passphrase);
+
+        results.push(json!({
+            "version": sig_data.version,
+            "derivation_path": sig_data.derivation_path,
+            "public_key": hex::encode(&derived),
+            "matches": stored.as_ref() == Some(&derived)
+        }));
+    }
+
+    Ok(warp::reply::json(&json!({ "id": id, "versions": results })).into_response())
+}
+
+/// Stores the master seed that signing keys are derived from, as when restoring a
+/// backup. Only possible before a seed has been generated or imported
+///
+/// ### Arguments
+///
+/// * `seed_payload` - Master seed payload
+/// * `signature_db` - Signature database
+pub async fn handle_master_seed_import(
+    seed_payload: MasterSeedPayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let seed = match hex::decode(seed_payload.seed.trim()) {
+        Ok(seed) if (MIN_SEED_LEN..=MAX_SEED_LEN).contains(&seed.len()) => seed,
+        _ => {
+            return Ok(error_reply(
+                "Seed must be 16 to 64 hex encoded bytes",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    match signature_db
+        .lock()
+        .await
+        .import_master_seed(&passphrase, &seed)
+        .await
+    {
+        Ok(true) => Ok(StatusCode::CREATED.into_response()),
+        Ok(false) => Ok(error_reply(
+            "A master seed is already stored",
+            StatusCode::CONFLICT,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Exports the public key of a signing keypair. The format is taken from the
+/// `format` query parameter, then the Accept header, and defaults to hex
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `query` - Export query parameters
+/// * `accept` - Accept header of the request
+/// * `signature_db` - Signature database
+pub async fn handle_key_public(
+    id: String,
+    query: PublicKeyQuery,
+    accept: Option<String>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let format = query
+        .format
+        .or(accept.as_deref().and_then(PublicKeyFormat::from_accept))
+        .unwrap_or(PublicKeyFormat::Hex);
+
+    let sig_db_lock = signature_db.lock().await;
+    let sig_data = match sig_db_lock.get_signature_data(id).await {
+        Ok(Some(sig_data)) => sig_data,
+        Ok(None) => return Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let pub_key = match sig_db_lock.get_public_key(&sig_data, &passphrase) {
+        Some(pub_key) => pub_key,
+        None => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            }))
+        }
+    };
+    match export_public_key(sig_data.algorithm, &pub_key, format, &sig_data.pk_hash) {
+        Some(exported) => {
+            Ok(
+                warp::reply::with_header(exported, "Content-Type", format.content_type())
+                    .into_response(),
+            )
+        }
+        None => Ok(error_reply(
+            "Keys of this algorithm cannot be exported in this format",
+            StatusCode::BAD_REQUEST,
+        )),
+    }
+}
+
+/// Publishes the public keys of all enabled signing keypairs as a JWKS. Versions that
+/// are disabled, retired, expired or superseded by a newer version stay listed for
+/// `JWKS_GRACE_PERIOD` from then on, so that existing tokens keep verifying. The
+/// server's own keypairs are left out. Decrypting every public key is slow, so the
+/// document is cached until a keypair changes or it is `JWKS_MAX_AGE` seconds old
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+pub async fn handle_jwks(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<impl Reply, Rejection> {
+    let now = unix_timestamp();
+    let sig_db_lock = signature_db.lock().await;
+    let document = match sig_db_lock.cached_jwks(now) {
+        Some(document) => document,
+        None => {
+            let generation = sig_db_lock.jwks_generation();
+            let entries = match sig_db_lock.list_signature_data().await {
+                Ok(entries) => entries,
+                Err(e) => return Err(warp::reject::custom(e)),
+            };
+
+            // The public keys are decrypted without holding up other requests
+            let sig_db = sig_db_lock.clone();
+            drop(sig_db_lock);
+            let created_at: HashMap<(&str, u32), u64> = entries
+                .iter()
+                .filter_map(|e| Some(((e.id.as_str(), e.version), e.created_at()?)))
+                .collect();
+            let keys: Vec<serde_json::Value> = entries
+                .iter()
+                .filter(|sig_data| !is_reserved_key_id(&sig_data.id))
+                .filter(|sig_data| {
+                    let superseded_at = created_at
+                        .get(&(sig_data.id.as_str(), sig_data.version + 1))
+                        .copied();
+                    let withdrawn_at = [
+                        sig_data.disabled.then_some(sig_data.disabled_at).flatten(),
+                        sig_data.retired_at,
+                        sig_data.not_after,
+                        superseded_at,
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .min();
+                    withdrawn_at.is_none_or(|at| now < at.saturating_add(JWKS_GRACE_PERIOD))
+                })
+                .filter_map(|sig_data| {
+                    let pub_key = sig_db.get_public_key(sig_data, &passphrase)?;
+                    public_key_to_jwk(sig_data.algorithm, &pub_key, &sig_data.pk_hash)
+                })
+                .collect();
+
+            let document = json!({ "keys": keys });
+            sig_db.cache_jwks(generation, document.clone(), now);
+            document
+        }
+    };
+
+    Ok(warp::reply::with_header(
+        warp::reply::json(&document),
+        "Cache-Control",
+        format!("public, max-age={}", JWKS_MAX_AGE),
+    ))
+}
+
+/// Deletes a signing keypair
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `signature_db` - Signature database
+pub async fn handle_key_delete(
+    id: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    match signature_db.lock().await.delete_key(&id).await {
+        Ok(true) => Ok(warp::reply::json(&json!({ "deleted": id })).into_response()),
+        Ok(false) => Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Disables a signing keypair so that it can no longer sign
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `signature_db` - Signature database
+pub async fn handle_key_disable(
+    id: String,
+    signature_db: Arc<Mutex<SignatureDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    match signature_db
+        .lock()
+        .await
+        .disable_key(&id, unix_timestamp())
+        .await
+    {
+        Ok(true) => Ok(warp::reply::json(&json!({ "id": id, "disabled": true })).into_response()),
+        Ok(false) => Ok(error_reply("Signing key not found", StatusCode::NOT_FOUND)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Verifies a message with the provided signature. If the payload carries a public
+/// key (hex, base64, PEM or JWK) it is used directly, otherwise the stored keypair
+/// for the ID is used
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `message_payload` - Message payload
+pub async fn handle_verify(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    message_payload: SigningDataPayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let id = message_payload.id.clone();
+    let encoding = message_payload.message_encoding.unwrap_or_default();
+    let message = match encoding.decode(&message_payload.message) {
+        Some(message) => message,
+        None => {
+            return Ok(error_reply(
+                "Failed to decode message",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let sig = match message_payload.signature {
+        Some(sig) => match signature_format(message_payload.signature_format)
+            .and_then(|format| format.decode(&sig))
+        {
+            Some(sig) => sig,
+            None => {
+                return Ok(error_reply(
+                    "Failed to decode signature",
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+        },
+        None => {
+            return Err(warp::reject::custom(DbError {
+                message: "Signature not provided".to_string(),
+            }));
+        }
+    };
+
+    let verification = match message_payload.public_key {
+        Some(public_key) => match parse_public_key(&public_key) {
+            Some((parsed, pub_key)) => {
+                // Only the payload can tell which padding an RSA key signs with
+                let algorithm = match message_payload.algorithm {
+                    Some(algorithm)
+                        if algorithm.rsa_padding().is_some() && parsed.rsa_padding().is_some() =>
+                    {
+                        algorithm
+                    }
+                    _ => parsed,
+                };
+                signing::verify(algorithm, &sig, &message, &pub_key)
+            }
+            None => {
+                return Ok(error_reply(
+                    "Failed to decode public key",
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        },
+        None => match signature_db
+            .lock()
+            .await
+            .verify_message(&id, &passphrase, message, &sig, unix_timestamp())
+            .await
+        {
+            Ok(Some(verification)) => verification,
+            Ok(None) => {
+                return Ok(error_reply(
+                    "No signing key exists for this id and no public key was provided",
+                    StatusCode::NOT_FOUND,
+                ))
+            }
+            Err(e) => return Err(warp::reject::custom(e)),
+        },
+    };
+
+    let response = json!({
+        "verification": verification,
+        "message_id": message_payload.id
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Issues a compact JWS over a claims object, signed with the newest version of an
+/// existing, enabled keypair. `iat` is always set; `exp` is set from `expires_in`, or the
+/// key's TTL, unless the claims already carry one
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+/// * `jwt_payload` - Claims and lifetime of the token
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+pub async fn handle_jwt_issue(
+    id: String,
+    jwt_payload: JwtIssuePayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    let now = unix_timestamp();
+    let sig_data = match sig_db_lock.get_signature_data(id.clone()).await {
+        Ok(Some(sig_data)) if sig_data.disabled => {
+            return Ok(error_reply(
+                "No enabled signing key exists for this id",
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Ok(Some(sig_data)) if sig_data.is_expired(now) => {
+            return Ok(error_reply(
+                "Signing key has expired",
+                StatusCode::FORBIDDEN,
+            ))
+        }
+        Ok(Some(sig_data)) if sig_data.algorithm.jws_alg().is_none() => {
+            return Ok(error_reply(
+                "Keys of this algorithm cannot sign JWTs",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+        Ok(Some(sig_data)) => sig_data,
+        Ok(None) => {
+            return Ok(error_reply(
+                "No enabled signing key exists for this id",
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let mut claims = jwt_payload.claims;
+    claims.insert("iat".to_string(), json!(now));
+    if !claims.contains_key("exp") {
+        let expires_in = jwt_payload.expires_in.unwrap_or(sig_data.ttl as u64);
+        let exp = match now.checked_add(expires_in) {
+            Some(exp) => exp,
+            None => {
+                return Ok(error_reply(
+                    "expires_in is too large",
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        };
+        claims.insert("exp".to_string(), json!(exp));
+    }
+
+    let token = match sign_jws(&sig_db_lock, &sig_data, "JWT", &claims, &passphrase).await {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            return Ok(error_reply(
+                "No enabled signing key exists for this id",
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    drop(sig_db_lock);
+    let log_index = log_signatures(&log_db, vec![jws_log_entry(&sig_data, &token, now)]).await?;
+
+    let response = json!({
+        "token": token,
+        "kid": sig_data.pk_hash,
+        "alg": sig_data.algorithm.jws_alg(),
+        "expires_at": claims.get("exp"),
+        "log_index": log_index[0]
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Verifies a compact JWS issued with a stored keypair, found through the `kid` header.
+/// The signature, `exp`, `nbf` and, if requested, `aud` are checked. Tokens with a `typ`
+/// other than `JWT`, or signed by one of the server's own keypairs, are not valid
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `verify_payload` - Token and expected audience
+pub async fn handle_jwt_verify(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    verify_payload: JwtVerifyPayload,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let invalid = |reason: &str| {
+        Ok(warp::reply::json(&json!({ "valid": false, "error": reason })).into_response())
+    };
+
+    let (signing_input, header, claims, signature) = match decode_jwt(&verify_payload.token) {
+        Some(decoded) => decoded,
+        None => return Ok(error_reply("Malformed JWT", StatusCode::BAD_REQUEST)),
+    };
+    if header.typ.as_deref().is_some_and(|typ| typ != "JWT") {
+        return invalid("Token is not a JWT");
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    let sig_data = match jws_signer(
+        &sig_db_lock,
+        &signing_input,
+        &header,
+        &signature,
+        &passphrase,
+    )
+    .await?
+    {
+        Ok(sig_data) => sig_data,
+        Err(reason) => return invalid(reason),
+    };
+    if is_reserved_key_id(&sig_data.id) {
+        return invalid("Token was not signed by a client key");
+    }
+
+    let now = unix_timestamp();
+    if let Some(exp) = claims.get("exp") {
+        if exp.as_u64().is_none_or(|exp| exp <= now) {
+            return invalid("JWT has expired");
+        }
+    }
+    if let Some(nbf) = claims.get("nbf") {
+        if nbf.as_u64().is_none_or(|nbf| nbf > now) {
+            return invalid("JWT is not valid yet");
+        }
+    }
+    if let Some(audience) = &verify_payload.audience {
+        let matches = match claims.get("aud") {
+            Some(serde_json::Value::String(aud)) => aud == audience,
+            Some(serde_json::Value::Array(auds)) => auds
+                .iter()
+                .any(|aud| aud.as_str() == Some(audience.as_str())),
+            _ => false,
+        };
+        if !matches {
+            return invalid("JWT audience does not match");
+        }
+    }
+
+    let response = json!({
+        "valid": true,
+        "id": sig_data.id,
+        "kid": sig_data.pk_hash,
+        "key_version": sig_data.version,
+        "claims": claims
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Issues a signed timestamp token over a client-supplied digest, proving that the
+/// digest existed at the time of issue. The token is a compact JWS with `typ`
+/// `tst+jwt`, signed by the dedicated timestamping key, so it can be checked offline
+/// against the key's exported public key. It carries the same
+/// information as an RFC 3161 `TimeStampToken` (digest, time, serial, nonce) but is
+/// not an RFC 3161 token and will not be accepted by RFC 3161 clients
+///
+/// ### Arguments
+///
+/// * `timestamp_payload` - Digest to timestamp
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+pub async fn handle_timestamp(
+    timestamp_payload: TimestampPayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let hash_algorithm = timestamp_payload.hash_algorithm.unwrap_or_default();
+    let digest = match hex::decode(timestamp_payload.digest.trim()) {
+        Ok(digest) if digest.len() == hash_algorithm.digest_len() => digest,
+        _ => {
+            return Ok(error_reply(
+                "Digest must be a hex encoded digest of the given hash algorithm",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let sig_db_lock = signature_db.lock().await;
+    if let Err(e) = sig_db_lock
+        .ensure_key(TIMESTAMP_KEY_ID, &passphrase, unix_timestamp())
+        .await
+    {
+        return Err(warp::reject::custom(e));
+    }
+    let sig_data = match sig_db_lock
+        .get_signature_data(TIMESTAMP_KEY_ID.to_string())
+        .await
+    {
+        Ok(Some(sig_data)) if !sig_data.disabled => sig_data,
+        Ok(_) => {
+            return Ok(error_reply(
+                "Timestamping key is disabled",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let serial = match sig_db_lock.next_serial(TIMESTAMP_KEY_ID).await {
+        Ok(serial) => serial,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let gen_time = unix_timestamp();
+    let mut claims = JwtClaims::new();
+    claims.insert("serial".to_string(), json!(serial));
+    claims.insert("iat".to_string(), json!(gen_time));
+    claims.insert("hash_algorithm".to_string(), json!(hash_algorithm));
+    claims.insert("digest".to_string(), json!(hex::encode(&digest)));
+    if let Some(nonce) = timestamp_payload.nonce {
+        claims.insert("nonce".to_string(), json!(nonce));
+    }
+
+    let token = match sign_jws(
+        &sig_db_lock,
+        &sig_data,
+        TIMESTAMP_TOKEN_TYPE,
+        &claims,
+        &passphrase,
+    )
+    .await
+    {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            return Ok(error_reply(
+                "Timestamping key is disabled",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    drop(sig_db_lock);
+    let entry = jws_log_entry(&sig_data, &token, gen_time);
+    let log_index = log_signatures(&log_db, vec![entry]).await?;
+
+    let response = json!({
+        "token": token,
+        "serial": serial,
+        "gen_time": gen_time,
+        "kid": sig_data.pk_hash,
+        "log_index": log_index[0]
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Verifies a timestamp token issued by `handle_timestamp` and, if a digest is
+/// given, that the token is for that digest
+///
+/// ### Arguments
+///
+/// * `verify_payload` - Token and optional digest
+/// * `signature_db` - Signature database
+pub async fn handle_timestamp_verify(
+    verify_payload: TimestampVerifyPayload,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let invalid = |reason: &str| {
+        Ok(warp::reply::json(&json!({ "valid": false, "error": reason })).into_response())
+    };
+
+    let (signing_input, header, claims, signature) = match decode_jwt(&verify_payload.token) {
+        Some(decoded) => decoded,
+        None => {
+            return Ok(error_reply(
+                "Malformed timestamp token",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    if header.typ.as_deref() != Some(TIMESTAMP_TOKEN_TYPE) {
+        return invalid("Token is not a timestamp token");
+    }
+
+    let sig_db_lock = signature_db.lock().await;
+    let sig_data = match jws_signer(
+        &sig_db_lock,
+        &signing_input,
+        &header,
+        &signature,
+        &passphrase,
+    )
+    .await?
+    {
+        Ok(sig_data) => sig_data,
+        Err(reason) => return invalid(reason),
+    };
+    if sig_data.id != TIMESTAMP_KEY_ID {
+        return invalid("Token was not signed by the timestamping key");
+    }
+
+    if let Some(digest) = verify_payload.digest {
+        let expected = claims.get("digest").and_then(|digest| digest.as_str());
+        if expected != Some(digest.trim().to_lowercase().as_str()) {
+            return invalid("Digest does not match the timestamp token");
+        }
+    }
+
+    let response = json!({
+        "valid": true,
+        "serial": claims.get("serial"),
+        "gen_time": claims.get("iat"),
+        "hash_algorithm": claims.get("hash_algorithm"),
+        "digest": claims.get("digest"),
+        "nonce": claims.get("nonce"),
+        "kid": sig_data.pk_hash,
+        "key_version": sig_data.version
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Gets the current signed tree head of the transparency log. The signature, made
+/// with the dedicated log key, covers the bytes described by `tree_head_input`
+///
+/// ### Arguments
+///
+/// * `log_db` - Transparency log database
+/// * `signature_db` - Signature database
+pub async fn handle_log_tree_head(
+    log_db: Arc<Mutex<LogDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let log_db_lock = log_db.lock().await;
+    let tree_size = match log_db_lock.tree_size().await {
+        Ok(tree_size) => tree_size,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let root_hash = match log_db_lock.root_hash(tree_size).await {
+        Ok(Some(root_hash)) => root_hash,
+        Ok(None) => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to compute log root hash".to_string(),
+            }))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    drop(log_db_lock);
+
+    let timestamp = unix_timestamp();
+    let sig_db_lock = signature_db.lock().await;
+    if let Err(e) = sig_db_lock
+        .ensure_key(LOG_KEY_ID, &passphrase, timestamp)
+        .await
+    {
+        return Err(warp::reject::custom(e));
+    }
+    let signed = match sig_db_lock
+        .sign_message(
+            LOG_KEY_ID,
+            &passphrase,
+            tree_head_input(timestamp, tree_size, &root_hash),
+            SignatureEncoding::default(),
+            timestamp,
+        )
+        .await
+    {
+        Ok(Some(signed)) => signed,
+        Ok(None) => {
+            return Ok(error_reply(
+                "Log signing key is disabled",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let response = json!({
+        "tree_size": tree_size,
+        "timestamp": timestamp,
+        "root_hash": hex::encode(root_hash),
+        "tree_head_signature": hex::encode(&signed.signature),
+        "key_id": LOG_KEY_ID,
+        "kid": hex::encode(sha3_256::digest(&signed.public_key)),
+        "key_version": signed.version,
+        "algorithm": signed.algorithm
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Gets an entry of the transparency log, with its leaf input and leaf hash
+///
+/// ### Arguments
+///
+/// * `leaf_index` - Index of the entry
+/// * `log_db` - Transparency log database
+pub async fn handle_log_entry(
+    leaf_index: u64,
+    log_db: Arc<Mutex<LogDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let leaf_input = match log_db.lock().await.get_entry(leaf_index).await {
+        Ok(Some(leaf_input)) => leaf_input,
+        Ok(None) => return Ok(error_reply("Log entry not found", StatusCode::NOT_FOUND)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let entry: serde_json::Value = serde_json::from_slice(&leaf_input).unwrap_or_default();
+
+    let response = json!({
+        "leaf_index": leaf_index,
+        "leaf_input": hex::encode(&leaf_input),
+        "leaf_hash": hex::encode(merkle::leaf_hash(&leaf_input)),
+        "entry": entry
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Gets the audit path proving that an entry is included in the tree of the given
+/// size, or of the current size if none is given
+///
+/// ### Arguments
+///
+/// * `query` - Leaf index and tree size
+/// * `log_db` - Transparency log database
+pub async fn handle_log_inclusion_proof(
+    query: InclusionProofQuery,
+    log_db: Arc<Mutex<LogDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let log_db_lock = log_db.lock().await;
+    let tree_size = match query.tree_size {
+        Some(tree_size) => tree_size,
+        None => match log_db_lock.tree_size().await {
+            Ok(tree_size) => tree_size,
+            Err(e) => return Err(warp::reject::custom(e)),
+        },
+    };
+
+    match log_db_lock
+        .inclusion_proof(query.leaf_index, tree_size)
+        .await
+    {
+        Ok(Some(audit_path)) => Ok(warp::reply::json(&json!({
+            "leaf_index": query.leaf_index,
+            "tree_size": tree_size,
+            "audit_path": audit_path.iter().map(hex::encode).collect::<Vec<_>>()
+        }))
+        .into_response()),
+        Ok(None) => Ok(error_reply(
+            "Leaf index or tree size is out of range",
+            StatusCode::BAD_REQUEST,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Gets the proof that the tree of size `first` is a prefix of the tree of size
+/// `second`, or of the current size if no second size is given
+///
+/// ### Arguments
+///
+/// * `query` - Tree sizes
+/// * `log_db` - Transparency log database
+pub async fn handle_log_consistency_proof(
+    query: ConsistencyProofQuery,
+    log_db: Arc<Mutex<LogDb>>,
+) -> Result<warp::reply::Response, Rejection> {
+    let log_db_lock = log_db.lock().await;
+    let second = match query.second {
+        Some(second) => second,
+        None => match log_db_lock.tree_size().await {
+            Ok(tree_size) => tree_size,
+            Err(e) => return Err(warp::reject::custom(e)),
+        },
+    };
+
+    match log_db_lock.consistency_proof(query.first, second).await {
+        Ok(Some(proof)) => Ok(warp::reply::json(&json!({
+            "first": query.first,
+            "second": second,
+            "consistency": proof.iter().map(hex::encode).collect::<Vec<_>>()
+        }))
+        .into_response()),
+        Ok(None) => Ok(error_reply(
+            "Tree sizes are out of range",
+            StatusCode::BAD_REQUEST,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Mints a signed, time-limited share link for a stored file
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the file to share
+/// * `share_payload` - Share link restrictions
+/// * `secret_db` - Secret database
+/// * `signature_db` - Signature database
+pub async fn handle_share_create(
+    file_name: String,
+    share_payload: SharePayload,
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    if let Some(ip) = &share_payload.ip {
+        if ip.parse::<IpAddr>().is_err() {
+            return Ok(error_reply(
+                "ip must be an IPv4 or IPv6 address",
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    }
+    let expires_at = match unix_timestamp().checked_add(share_payload.expires_in) {
+        Some(expires_at) => expires_at,
+        None => {
+            return Ok(error_reply(
+                "expires_in is too large",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    match secret_db
+        .lock()
+        .await
+        .get_secret(&file_name, &passphrase)
+        .await
+    {
+        Ok(sec_entry) if sec_entry.is_expired(unix_timestamp()) => {
+            return Ok(error_reply("File has expired", StatusCode::GONE))
+        }
+        Ok(_) => {}
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+
+    let claims = ShareToken {
+        share_id: hex::encode(generate_random::<16>()),
+        file_name,
+        expires_at,
+        max_downloads: share_payload.max_downloads,
+        ip: share_payload.ip,
+    };
+    let encoded_claims = encode_share_claims(&claims);
+
+    let sig_db_lock = signature_db.lock().await;
+    if let Err(e) = sig_db_lock
+        .ensure_key(SHARE_KEY_ID, &passphrase, unix_timestamp())
+        .await
+    {
+        return Err(warp::reject::custom(e));
+    }
+    let signature = match sig_db_lock
+        .sign_message(
+            SHARE_KEY_ID,
+            &passphrase,
+            encoded_claims.clone().into(),
+            SignatureEncoding::default(),
+            unix_timestamp(),
+        )
+        .await
+    {
+        Ok(Some(signed)) => signed.signature,
+        Ok(None) => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to sign share link".to_string(),
+            }));
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    let token = format!("{}.{}", encoded_claims, URL_SAFE_NO_PAD.encode(&signature));
+    let response = json!({
+        "token": token,
+        "url": format!("/s/{}", token),
+        "expires_at": claims.expires_at,
+        "max_downloads": claims.max_downloads
+    });
+
+    Ok(warp::reply::json(&response).into_response())
+}
+
+/// Streams the decrypted file behind a share link token
+///
+/// ### Arguments
+///
+/// * `token` - Share link token
+/// * `remote` - Address of the requesting client
+/// * `secret_db` - Secret database
+/// * `signature_db` - Signature database
+pub async fn handle_share_download(
+    token: String,
+    remote: Option<SocketAddr>,
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let (encoded_claims, claims, signature) = match decode_share_token(&token) {
+        Some(decoded) => decoded,
+        None => {
+            return Ok(error_reply(
+                "Malformed share token",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let sec_db_lock = secret_db.lock().await;
+    let verification = signature_db
+        .lock()
+        .await
+        .verify_message(
+            SHARE_KEY_ID,
+            &passphrase,
+            encoded_claims.into(),
+            signature.as_ref(),
+            unix_timestamp(),
+        )
+        .await;
+    if !matches!(verification, Ok(Some(true))) {
+        return Ok(error_reply("Invalid share token", StatusCode::FORBIDDEN));
+    }
+
+    if claims.expires_at <= unix_timestamp() {
+        return Ok(error_reply("Share link has expired", StatusCode::GONE));
+    }
+
+    if let Some(ip) = &claims.ip {
+        // IPv4 clients can show up as IPv4-mapped IPv6 addresses
+        let allowed = match (remote, ip.parse::<IpAddr>()) {
+            (Some(addr), Ok(ip)) => addr.ip().to_canonical() == ip.to_canonical(),
+            _ => false,
+        };
+        if !allowed {
+            return Ok(error_reply(
+                "Share link is not valid from this address",
+                StatusCode::FORBIDDEN,
+            ));
+        }
+    }
+
+    let sec_entry = match sec_db_lock.get_secret(&claims.file_name, &passphrase).await {
+        Ok(entry) => entry,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if sec_entry.is_expired(unix_timestamp()) {
+        return Ok(error_reply("File has expired", StatusCode::GONE));
+    }
+
+    let reader = match sec_db_lock
+        .open_ciphertext(&claims.file_name, sec_entry.inline, 0)
+        .await
+    {
+        Ok(reader) => reader,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    // Only downloads that can actually be served count against the limit
+    match sec_db_lock
+        .record_share_download(&claims.share_id, claims.max_downloads)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(error_reply(
+                "Share link download limit reached",
+                StatusCode::GONE,
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+    let stream = decrypted_stream(
+        reader,
+        sec_entry.key,
+        sec_entry.nonce,
+        sec_entry.compression,
+        sec_entry.format,
+    );
+
+    Ok(warp::http::Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .body(warp::hyper::Body::wrap_stream(stream))
+        .unwrap())
+}
+
+/// Stores a one-time secret and returns its retrieval token
+///
+/// ### Arguments
+///
+/// * `secret_payload` - Secret payload
+/// * `secret_db` - Secret database
+pub async fn handle_burn_create(
+    secret_payload: BurnSecretPayload,
+    secret_db: Arc<Mutex<SecretDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let expires_at = match secret_payload.ttl {
+        Some(ttl) => match unix_timestamp().checked_add(ttl) {
+            Some(expires_at) => Some(expires_at),
+            None => return Ok(error_reply("ttl is too large", StatusCode::BAD_REQUEST)),
+        },
+        None => None,
+    };
+    let token = hex::encode(generate_random::<32>());
+    let passphrase = combine_passphrase(&passphrase, secret_payload.passphrase.as_deref());
+
+    match secret_db
+        .lock()
+        .await
+        .insert_burn_secret(
+            &token,
+            &passphrase,
+            secret_payload.secret.into(),
+            expires_at,
+        )
+        .await
+    {
+        Ok(_) => Ok(warp::reply::json(&json!({
+            "token": token,
+            "expires_at": expires_at
+        }))
+        .into_response()),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Reveals a one-time secret, destroying it in the process
+///
+/// ### Arguments
+///
+/// * `token` - Retrieval token of the secret
+/// * `user_passphrase` - Optional passphrase the secret was protected with
+/// * `secret_db` - Secret database
+pub async fn handle_burn_read(
+    token: String,
+    user_passphrase: Option<String>,
+    secret_db: Arc<Mutex<SecretDb>>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let passphrase = combine_passphrase(&passphrase, user_passphrase.as_deref());
+    let burn_read = secret_db
+        .lock()
+        .await
+        .take_burn_secret(&token, &passphrase, unix_timestamp())
+        .await;
+
+    match burn_read {
+        Ok(BurnRead::Revealed(secret)) => Ok(warp::reply::json(&json!({
+            "secret": String::from_utf8_lossy(&secret)
+        }))
+        .into_response()),
+        Ok(BurnRead::Gone) => Ok(error_reply(
+            "Secret has already been read or has expired",
+            StatusCode::GONE,
+        )),
+        Ok(BurnRead::Denied) => Ok(error_reply(
+            "Failed to decrypt secret",
+            StatusCode::FORBIDDEN,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Builds the public metadata of a signing keypair
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `sig_data` - Signature entry
+fn key_metadata(
+    signature_db: &SignatureDb,
+    sig_data: &SignatureEntry,
+    passphrase: &str,
+) -> Option<serde_json::Value> {
+    let pub_key = signature_db.get_public_key(sig_data, passphrase)?;
+
+    Some(json!({
+        "id": sig_data.id,
+        "pk_hash": sig_data.pk_hash,
+        "public_key": hex::encode(pub_key),
+        "version": sig_data.version,
+        "algorithm": sig_data.algorithm,
+        "ttl": sig_data.ttl,
+        "derivation_path": sig_data.derivation_path,
+        "created_at": sig_data.created_at(),
+        "not_after": sig_data.not_after,
+        "expired": sig_data.is_expired(unix_timestamp()),
+        "disabled": sig_data.disabled,
+        "retired_at": sig_data.retired_at
+    }))
+}
+
+/// Lifetime of a keypair created now from the requested TTL and `not_after` time,
+/// defaulting to `SIG_TTL`. Returns None if `not_after` has already passed or is
+/// more than `MAX_KEY_LIFETIME` away
+///
+/// ### Arguments
+///
+/// * `ttl` - Seconds the key can sign for, 0 for no expiry
+/// * `not_after` - UNIX time after which the key can no longer sign
+fn key_lifetime(ttl: Option<u32>, not_after: Option<u64>) -> Option<KeyLifetime> {
+    let now = unix_timestamp();
+    if not_after.is_some_and(|at| at <= now || at - now > MAX_KEY_LIFETIME) {
+        return None;
+    }
+
+    Some(KeyLifetime::new(now, ttl.unwrap_or(SIG_TTL), not_after))
+}
+
+/// Builds the response for a signing request whose keypair could not sign, telling
+/// an expired keypair apart from a missing or disabled one
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `id` - ID of the keypair
+async fn unusable_key_reply(
+    signature_db: &Arc<Mutex<SignatureDb>>,
+    id: &str,
+) -> Result<warp::reply::Response, Rejection> {
+    match signature_db
+        .lock()
+        .await
+        .get_signature_data(id.to_string())
+        .await
+    {
+        Ok(Some(sig_data)) if !sig_data.disabled && sig_data.is_expired(unix_timestamp()) => Ok(
+            error_reply("Signing key has expired", StatusCode::FORBIDDEN),
+        ),
+        Ok(_) => Ok(error_reply(
+            "No enabled signing key exists for this id",
+            StatusCode::NOT_FOUND,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Builds the response for a request that tries to use or manage one of the
+/// server's own keypairs
+fn reserved_key_reply() -> warp::reply::Response {
+    error_reply(RESERVED_KEY_ERROR, StatusCode::FORBIDDEN)
+}
+
+/// Checks that an ID has an Ed25519 keypair, as attached signatures need one. Returns
+/// the error response to send if it does not
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `id` - ID of the keypair
+/// * `signing` - Whether the keypair is about to sign, so must be enabled
+async fn attached_key_check(
+    signature_db: &SignatureDb,
+    id: &str,
+    signing: bool,
+) -> Result<Option<warp::reply::Response>, Rejection> {
+    match signature_db.get_signature_data(id.to_string()).await {
+        Ok(Some(sig_data)) if signing && sig_data.disabled => Ok(Some(error_reply(
+            "No enabled signing key exists for this id",
+            StatusCode::NOT_FOUND,
+        ))),
+        Ok(Some(sig_data)) if signing && sig_data.is_expired(unix_timestamp()) => Ok(Some(
+            error_reply("Signing key has expired", StatusCode::FORBIDDEN),
+        )),
+        Ok(Some(sig_data)) if sig_data.algorithm != SigningAlgorithm::Ed25519 => {
+            Ok(Some(error_reply(
+                "Attached signatures need an Ed25519 signing key",
+                StatusCode::BAD_REQUEST,
+            )))
+        }
+        Ok(Some(_)) => Ok(None),
+        Ok(None) => Ok(Some(error_reply(
+            "No signing key exists for this id",
+            StatusCode::NOT_FOUND,
+        ))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Signs a message, records the signature in the transparency log and builds the
+/// response of the sign endpoints
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+/// * `log_db` - Transparency log database
+/// * `id` - ID of the keypair
+/// * `message` - Message to sign
+/// * `encoding` - Encoding of ECDSA signatures
+/// * `format` - Text encoding of the signature in the response
+async fn sign_reply(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    id: String,
+    message: Vec<u8>,
+    encoding: SignatureEncoding,
+    format: Option<DataEncoding>,
+    passphrase: String,
+) -> Result<warp::reply::Response, Rejection> {
+    let format = match signature_format(format) {
+        Some(format) => format,
+        None => {
+            return Ok(error_reply(
+                "Signatures cannot be encoded as UTF-8",
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    if is_reserved_key_id(&id) {
+        return Ok(reserved_key_reply());
+    }
+
+    let sign_result = signature_db
+        .lock()
+        .await
+        .sign_message(
+            &id,
+            &passphrase,
+            message.clone(),
+            encoding,
+            unix_timestamp(),
+        )
+        .await;
+
+    match sign_result {
+        Ok(Some(signed)) => {
+            let entry = LogEntry::new(
+                &id,
+                signed.version,
+                signed.algorithm,
+                &signed.signature,
+                &message,
+                unix_timestamp(),
+            );
+            let log_index = log_signatures(&log_db, vec![entry]).await?;
+            let response = json!({
+                "signature": format.encode(&signed.signature),
+                "public_key": signed.public_key,
+                "key_version": signed.version,
+                "algorithm": signed.algorithm,
+                "message_id": id,
+                "log_index": log_index[0]
+            });
+
+            Ok(warp::reply::json(&response).into_response())
+        }
+        Ok(None) => unusable_key_reply(&signature_db, &id).await,
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Appends issued signatures to the transparency log and returns their leaf indices.
+/// Every signature a client asks for is logged. Share links and signed tree heads are
+/// not, as they are signed by the server's own keypairs for its own use
+///
+/// ### Arguments
+///
+/// * `log_db` - Transparency log database
+/// * `entries` - Log entries of the signatures
+async fn log_signatures(
+    log_db: &Arc<Mutex<LogDb>>,
+    entries: Vec<LogEntry>,
+) -> Result<Vec<u64>, Rejection> {
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    match log_db.lock().await.append_entries(entries).await {
+        Ok(indices) => Ok(indices),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Log entry of a compact JWS issued with a stored keypair, logging its signing input
+/// as the signed message
+///
+/// ### Arguments
+///
+/// * `sig_data` - Signature entry of the keypair
+/// * `token` - Issued compact JWS
+/// * `timestamp` - UNIX time the JWS was issued
+fn jws_log_entry(sig_data: &SignatureEntry, token: &str, timestamp: u64) -> LogEntry {
+    let (signing_input, signature) = token.rsplit_once('.').unwrap_or_default();
+    let signature = URL_SAFE_NO_PAD.decode(signature).unwrap_or_default();
+
+    LogEntry::new(
+        &sig_data.id,
+        sig_data.version,
+        sig_data.algorithm,
+        &signature,
+        signing_input.as_bytes(),
+        timestamp,
+    )
+}
+
+/// Signs claims as a compact JWS with a stored keypair, with the key's `pk_hash`
+/// as `kid`. Returns None if the keypair can no longer sign, or its algorithm has
+/// no JWS name
+///
+/// ### Arguments
+///
+/// * `sig_db` - Signature database
+/// * `sig_data` - Signature entry of the keypair
+/// * `typ` - Value of the `typ` header
+/// * `claims` - Claims to sign
+/// * `passphrase` - Passphrase to derive encryption keys from
+async fn sign_jws(
+    sig_db: &SignatureDb,
+    sig_data: &SignatureEntry,
+    typ: &str,
+    claims: &JwtClaims,
+    passphrase: &str,
+) -> Result<Option<String>, DbError> {
+    let alg = match sig_data.algorithm.jws_alg() {
+        Some(alg) => alg,
+        None => return Ok(None),
+    };
+    let header = JwtHeader {
+        alg: alg.to_string(),
+        typ: Some(typ.to_string()),
+        kid: Some(sig_data.pk_hash.clone()),
+    };
+    let signing_input = format!(
+        "{}.{}",
+        encode_jwt_segment(&header),
+        encode_jwt_segment(claims)
+    );
+
+    // JWS wants ECDSA signatures as fixed-size r || s
+    let signed = sig_db
+        .sign_message(
+            &sig_data.id,
+            passphrase,
+            signing_input.clone().into(),
+            SignatureEncoding::Fixed,
+            unix_timestamp(),
+        )
+        .await?;
+
+    Ok(signed.map(|signed| {
+        format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signed.signature)
+        )
+    }))
+}
+
+/// Finds the stored keypair a compact JWS claims to be signed with, through its
+/// `kid` header, and checks the signature. Returns the reason if the JWS is invalid
+///
+/// ### Arguments
+///
+/// * `sig_db` - Signature database
+/// * `signing_input` - Encoded header and claims
+/// * `header` - Decoded header
+/// * `signature` - Decoded signature
+/// * `passphrase` - Passphrase to derive encryption keys from
+async fn jws_signer(
+    sig_db: &SignatureDb,
+    signing_input: &str,
+    header: &JwtHeader,
+    signature: &[u8],
+    passphrase: &str,
+) -> Result<Result<SignatureEntry, &'static str>, Rejection> {
+    let kid = match &header.kid {
+        Some(kid) => kid,
+        None => return Ok(Err("Token has no kid header")),
+    };
+
+    let sig_data = match sig_db.get_signature_data_by_pk_hash(kid).await {
+        Ok(Some(sig_data)) => sig_data,
+        Ok(None) => return Ok(Err("Unknown signing key")),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if sig_data.retired_at.is_some() {
+        return Ok(Err("Signing key has been retired"));
+    }
+    if !sig_data.verifies_at(unix_timestamp()) {
+        return Ok(Err("Signing key has expired"));
+    }
+    if Some(header.alg.as_str()) != sig_data.algorithm.jws_alg() {
+        return Ok(Err("Token algorithm does not match the signing key"));
+    }
+    let fixed_len = sig_data
+        .algorithm
+        .ecdsa_curve()
+        .map(|c| c.fixed_signature_len());
+    if fixed_len.is_some_and(|len| len != signature.len()) {
+        return Ok(Err("Invalid signature"));
+    }
+
+    let pub_key = match sig_db.get_public_key(&sig_data, passphrase) {
+        Some(pub_key) => pub_key,
+        None => {
+            return Err(warp::reject::custom(DbError {
+                message: "Failed to decrypt public key".to_string(),
+            }))
+        }
+    };
+    if !signing::verify(
+        sig_data.algorithm,
+        signature,
+        signing_input.as_bytes(),
+        &pub_key,
+    ) {
+        return Ok(Err("Invalid signature"));
+    }
+
+    Ok(Ok(sig_data))
+}
+
+/// Computes the SHA3-256 digest of a stored file's plaintext, or None if there is
+/// no such file or it has expired
+///
+/// ### Arguments
+///
+/// * `sec_db` - Secret database
+/// * `file_name` - Name of the file
+/// * `passphrase` - Passphrase to derive encryption keys from
+async fn file_digest(
+    sec_db: &SecretDb,
+    file_name: &str,
+    passphrase: &str,
+) -> Result<Option<Vec<u8>>, Rejection> {
+    match sec_db.get_secret_entry(file_name).await {
+        Ok(Some(entry)) if !entry.is_expired(unix_timestamp()) => {}
+        Ok(_) => return Ok(None),
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+
+    let sec_entry = match sec_db.get_secret(file_name, passphrase).await {
+        Ok(entry) => entry,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let reader = match sec_db.open_ciphertext(file_name, sec_entry.inline, 0).await {
+        Ok(reader) => reader,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    match decrypted_digest(
+        reader,
+        sec_entry.key,
+        sec_entry.nonce,
+        sec_entry.compression,
+        sec_entry.format,
+    )
+    .await
+    {
+        Ok(digest) => Ok(Some(digest.to_vec())),
+        Err(_) => Err(warp::reject::custom(DbError {
+            message: "Failed to decrypt file".to_string(),
+        })),
+    }
+}
+
+/// JSON description of a file signature
+///
+/// ### Arguments
+///
+/// * `file_name` - Name of the signed file
+/// * `file_signature` - Signature over the file digest
+/// * `format` - Signature output encoding
+fn file_signature_json(
+    file_name: &str,
+    file_signature: &FileSignature,
+    format: DataEncoding,
+) -> serde_json::Value {
+    json!({
+        "file_name": file_name,
+        "key_id": file_signature.key_id,
+        "key_version": file_signature.key_version,
+        "algorithm": file_signature.algorithm,
+        "digest": hex::encode(&file_signature.digest),
+        "signature": format.encode(&file_signature.signature),
+        "signed_at": file_signature.signed_at
+    })
+}
+
+/// Text encoding of a signature, hex unless one is requested. Signatures are not
+/// text, so None is returned if UTF-8 is requested
+///
+/// ### Arguments
+///
+/// * `format` - Requested encoding
+fn signature_format(format: Option<DataEncoding>) -> Option<DataEncoding> {
+    match format.unwrap_or(DataEncoding::Hex) {
+        DataEncoding::Utf8 => None,
+        format => Some(format),
+    }
+}
+
+/// Builds a JSON error response with the given status code
+///
+/// ### Arguments
+///
+/// * `message` - Error message
+/// * `status` - Response status code
+fn error_reply(message: &str, status: StatusCode) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message })), status)
+        .into_response()
+}
@@ -1,5 +1,53 @@
+use crate::crypto::key_format::PublicKeyFormat;
+use crate::crypto::sign_ecdsa::SignatureEncoding;
+use crate::crypto::signing::SigningAlgorithm;
+use crate::db::secret_db::Compression;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
 use serde::Deserialize;
 
+/// Text encodings of binary messages and signatures in request and response bodies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataEncoding {
+    #[default]
+    Utf8,
+    Hex,
+    Base64,
+    Base64url,
+}
+
+impl DataEncoding {
+    /// Decodes text in this encoding to bytes
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Encoded data
+    pub fn decode(&self, data: &str) -> Option<Vec<u8>> {
+        match self {
+            DataEncoding::Utf8 => Some(data.as_bytes().to_vec()),
+            DataEncoding::Hex => hex::decode(data).ok(),
+            DataEncoding::Base64 => STANDARD.decode(data).ok(),
+            DataEncoding::Base64url => URL_SAFE_NO_PAD.decode(data.trim_end_matches('=')).ok(),
+        }
+    }
+
+    /// Encodes bytes as text in this encoding. Returns None for bytes that are
+    /// not valid UTF-8 text
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Bytes to encode
+    pub fn encode(&self, data: &[u8]) -> Option<String> {
+        match self {
+            DataEncoding::Utf8 => String::from_utf8(data.to_vec()).ok(),
+            DataEncoding::Hex => Some(hex::encode(data)),
+            DataEncoding::Base64 => Some(STANDARD.encode(data)),
+            DataEncoding::Base64url => Some(URL_SAFE_NO_PAD.encode(data)),
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ChunkMetadataPayload {
     pub file_name: String,
@@ -7,6 +55,9 @@ pub struct ChunkMetadataPayload {
     pub total_chunks: usize,
     pub timestamp: String,
     pub custom_data: Option<String>,
+    pub ttl: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub compression: Option<Compression>,
 }
 
 #[derive(serde::Deserialize)]
@@ -21,5 +72,264 @@ pub struct SigningDataPayload {
     pub message: String,
     pub timestamp: String,
     pub signature: Option<String>,
+    pub public_key: Option<String>,
     pub custom_data: Option<String>,
+    pub signature_encoding: Option<SignatureEncoding>,
+    pub algorithm: Option<SigningAlgorithm>,
+    pub message_encoding: Option<DataEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RawSignQuery {
+    pub id: String,
+    pub signature_encoding: Option<SignatureEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BatchSignItem {
+    pub id: String,
+    pub message: String,
+    pub signature_encoding: Option<SignatureEncoding>,
+    pub message_encoding: Option<DataEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BatchVerifyItem {
+    pub id: String,
+    pub message: String,
+    pub signature: String,
+    pub message_encoding: Option<DataEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AttachedSignPayload {
+    pub id: String,
+    pub message: String,
+    pub message_encoding: Option<DataEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AttachedVerifyPayload {
+    pub id: String,
+    pub signed_message: String,
+    pub signature_format: Option<DataEncoding>,
+    pub message_encoding: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SshsigSignPayload {
+    pub id: String,
+    pub namespace: String,
+    pub message: String,
+    pub message_encoding: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SshsigVerifyPayload {
+    /// Armored signature, as produced by `ssh-keygen -Y sign`
+    pub signature: String,
+    pub namespace: String,
+    pub message: String,
+    pub message_encoding: Option<DataEncoding>,
+    /// Principal the signature must be from. If omitted, every principal whose
+    /// key made the signature is reported
+    pub principal: Option<String>,
+    /// Contents of an allowed_signers file
+    pub allowed_signers: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SharePayload {
+    pub expires_in: u64,
+    pub max_downloads: Option<u32>,
+    pub ip: Option<String>,
+}
+
+/// Claims carried inside a signed share link token
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ShareToken {
+    pub share_id: String,
+    pub file_name: String,
+    pub expires_at: u64,
+    pub max_downloads: Option<u32>,
+    pub ip: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BurnSecretPayload {
+    pub secret: String,
+    pub passphrase: Option<String>,
+    pub ttl: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LegalHoldPayload {
+    pub legal_hold: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FileSignPayload {
+    pub key_id: String,
+    pub signature_encoding: Option<SignatureEncoding>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+/// Verifies the stored signature of a file, or a supplied one if `key_id` and
+/// `signature` are both given
+#[derive(serde::Deserialize)]
+pub struct FileVerifyPayload {
+    pub key_id: Option<String>,
+    pub signature: Option<String>,
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FileSignatureQuery {
+    pub signature_format: Option<DataEncoding>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct KeyCreatePayload {
+    pub id: String,
+    pub algorithm: Option<SigningAlgorithm>,
+    pub key_size: Option<usize>,
+    /// Seconds the key can sign for, 0 for a key that never expires
+    pub ttl: Option<u32>,
+    /// UNIX time after which the key can no longer sign, overriding the TTL
+    pub not_after: Option<u64>,
+    /// Derive an Ed25519 key from the master seed instead of generating one
+    pub derived: Option<bool>,
+    /// SLIP-0010 path to derive the key at, such as `m/44'/0'/0'`. Implies `derived`
+    pub derivation_path: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PolicyPayload {
+    pub name: String,
+    pub threshold: usize,
+    pub signers: Vec<PolicySignerPayload>,
+}
+
+/// Signer of a verification policy. Exactly one of `public_key`, as hex, PEM, JWK
+/// or OpenSSH Ed25519 public key, and `key_id`, naming a stored Ed25519 keypair,
+/// must be given
+#[derive(serde::Deserialize)]
+pub struct PolicySignerPayload {
+    pub name: String,
+    pub public_key: Option<String>,
+    pub key_id: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PolicyVerifyPayload {
+    pub policy: String,
+    pub message: String,
+    pub message_encoding: Option<DataEncoding>,
+    pub signature_format: Option<DataEncoding>,
+    pub signatures: Vec<PolicySignaturePayload>,
+}
+
+/// Signature for a policy check, optionally naming the signer it is from
+#[derive(serde::Deserialize)]
+pub struct PolicySignaturePayload {
+    pub signer: Option<String>,
+    pub signature: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MasterSeedPayload {
+    /// Hex encoded seed of 16 to 64 bytes
+    pub seed: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublicKeyQuery {
+    pub format: Option<PublicKeyFormat>,
+}
+
+/// Protected header of a compact JWS
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JwtHeader {
+    pub alg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+/// Hash function a client used to compute the digest it wants timestamped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DigestAlgorithm {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha3_256,
+    Sha3_384,
+    Sha3_512,
+}
+
+impl DigestAlgorithm {
+    /// Length in bytes of a digest
+    pub fn digest_len(&self) -> usize {
+        match self {
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha3_256 => 32,
+            DigestAlgorithm::Sha384 | DigestAlgorithm::Sha3_384 => 48,
+            DigestAlgorithm::Sha512 | DigestAlgorithm::Sha3_512 => 64,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TimestampPayload {
+    pub digest: String,
+    pub hash_algorithm: Option<DigestAlgorithm>,
+    pub nonce: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TimestampVerifyPayload {
+    pub token: String,
+    pub digest: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct InclusionProofQuery {
+    pub leaf_index: u64,
+    pub tree_size: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConsistencyProofQuery {
+    pub first: u64,
+    pub second: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct JwtIssuePayload {
+    pub claims: serde_json::Map<String, serde_json::Value>,
+    pub expires_in: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct JwtVerifyPayload {
+    pub token: String,
+    pub audience: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct KeyImportPayload {
+    pub private_key: String,
+    pub password: Option<String>,
+    pub algorithm: Option<SigningAlgorithm>,
+    /// Seconds the key can sign for, 0 for a key that never expires
+    pub ttl: Option<u32>,
+    /// UNIX time after which the key can no longer sign, overriding the TTL
+    pub not_after: Option<u64>,
 }
@@ -1,4 +1,5 @@
 pub mod handlers;
 pub mod interfaces;
 pub mod routes;
-pub mod utils;
\ No newline at end of file
+pub mod tasks;
+pub mod utils;
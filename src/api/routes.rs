@@ -1,5 +1,18 @@
-use super::handlers::{handle_download, handle_sign, handle_upload_raw, handle_verify};
-use super::utils::{post_cors, with_node_component};
+use super::handlers::{
+    handle_burn_create, handle_burn_read, handle_delete_file, handle_download, handle_file_sign,
+    handle_file_signature, handle_file_verify, handle_jwks, handle_jwt_issue, handle_jwt_verify,
+    handle_key_create, handle_key_delete, handle_key_derivation, handle_key_disable,
+    handle_key_get, handle_key_import, handle_key_public, handle_key_retire, handle_key_rotate,
+    handle_legal_hold, handle_log_consistency_proof, handle_log_entry, handle_log_inclusion_proof,
+    handle_log_tree_head, handle_master_seed_import, handle_policy_create, handle_policy_delete,
+    handle_policy_get, handle_share_create, handle_share_download, handle_sign,
+    handle_sign_attached, handle_sign_batch, handle_sign_raw, handle_sign_sshsig, handle_timestamp,
+    handle_timestamp_verify, handle_upload_raw, handle_verify, handle_verify_attached,
+    handle_verify_batch, handle_verify_policy, handle_verify_sshsig,
+};
+use super::utils::{delete_cors, get_cors, post_cors, with_node_component};
+use crate::db::constants::MAX_BURN_SECRET_LEN;
+use crate::db::log_db::LogDb;
 use crate::db::secret_db::SecretDb;
 use crate::db::sign_db::SignatureDb;
 use futures::lock::Mutex;
@@ -8,7 +21,8 @@ use warp::{Filter, Rejection, Reply};
 
 /// POST /upload
 ///
-/// Uploads a chunk of byte data to the server
+/// Uploads a chunk of byte data to the server. The chunk metadata goes in the query
+/// string, as the body can only be read once and holds the raw chunk
 pub fn upload_raw(
     secret_db: Arc<Mutex<SecretDb>>,
     passphrase: String,
@@ -17,7 +31,7 @@ pub fn upload_raw(
         .and(warp::path("upload"))
         .and(with_node_component(secret_db))
         .and(with_node_component(passphrase))
-        .and(warp::body::json())
+        .and(warp::query())
         .and(warp::body::bytes())
         .and_then(move |db, pp, metadata, chunk| handle_upload_raw(metadata, chunk, db, pp))
         .with(post_cors())
@@ -44,14 +58,40 @@ pub fn download(
 /// Signs a message with the private key of the public key hash
 pub fn sign(
     sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
     passphrase: String,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     warp::post()
         .and(warp::path("sign"))
         .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
         .and(with_node_component(passphrase))
         .and(warp::body::json())
-        .and_then(move |db, pp, signing_data| handle_sign(db, signing_data, pp))
+        .and_then(move |db, log, pp, signing_data| handle_sign(db, log, signing_data, pp))
+        .with(post_cors())
+}
+
+/// POST /sign, with an `application/octet-stream` body
+///
+/// Signs the raw request body with the private key of the ID in the query
+pub fn sign_raw(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path("sign"))
+        .and(warp::path::end())
+        .and(warp::header::exact_ignore_case(
+            "content-type",
+            "application/octet-stream",
+        ))
+        .and(warp::query())
+        .and(warp::body::bytes())
+        .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_sign_raw)
         .with(post_cors())
 }
 
@@ -70,3 +110,1210 @@ pub fn verify(
         .and_then(move |db, pp, signing_data| handle_verify(db, signing_data, pp))
         .with(post_cors())
 }
+
+/// POST /verify/policy
+///
+/// Checks signatures over a message against a verification policy
+pub fn verify_policy(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("verify" / "policy"))
+        .and(with_node_component(sig_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_verify_policy)
+        .with(post_cors())
+}
+
+/// POST /policies
+///
+/// Creates a named verification policy
+pub fn policy_create(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path("policies"))
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(with_node_component(sig_db))
+        .and_then(handle_policy_create)
+        .with(post_cors())
+}
+
+/// GET /policies/{name}
+///
+/// Gets a verification policy
+pub fn policy_get(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("policies" / String))
+        .and(with_node_component(sig_db))
+        .and_then(handle_policy_get)
+        .with(get_cors())
+}
+
+/// DELETE /policies/{name}
+///
+/// Deletes a verification policy
+pub fn policy_delete(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::delete()
+        .and(warp::path!("policies" / String))
+        .and(with_node_component(sig_db))
+        .and_then(handle_policy_delete)
+        .with(delete_cors())
+}
+
+/// POST /keys/{id}/jwt
+///
+/// Issues a JWT signed with a signing keypair
+pub fn jwt_issue(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / String / "jwt"))
+        .and(warp::body::json())
+        .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_jwt_issue)
+        .with(post_cors())
+}
+
+/// POST /jwt/verify
+///
+/// Verifies a JWT issued with a stored signing keypair
+pub fn jwt_verify(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("jwt" / "verify"))
+        .and(with_node_component(sig_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_jwt_verify)
+        .with(post_cors())
+}
+
+/// POST /sign/attached
+///
+/// Signs a message and returns it with the signature attached
+pub fn sign_attached(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("sign" / "attached"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_sign_attached)
+        .with(post_cors())
+}
+
+/// POST /verify/attached
+///
+/// Verifies a message with its signature attached and recovers the message
+pub fn verify_attached(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("verify" / "attached"))
+        .and(with_node_component(sig_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_verify_attached)
+        .with(post_cors())
+}
+
+/// POST /sign/sshsig
+///
+/// Signs a message as an armored SSH signature
+pub fn sign_sshsig(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("sign" / "sshsig"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_sign_sshsig)
+        .with(post_cors())
+}
+
+/// POST /verify/sshsig
+///
+/// Verifies an armored SSH signature against an allowed_signers list
+pub fn verify_sshsig() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("verify" / "sshsig"))
+        .and(warp::body::json())
+        .and_then(handle_verify_sshsig)
+        .with(post_cors())
+}
+
+/// POST /sign/batch
+///
+/// Signs a batch of messages
+pub fn sign_batch(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("sign" / "batch"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(log_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_sign_batch)
+        .with(post_cors())
+}
+
+/// POST /verify/batch
+///
+/// Verifies a batch of messages with their signatures
+pub fn verify_batch(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("verify" / "batch"))
+        .and(with_node_component(sig_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_verify_batch)
+        .with(post_cors())
+}
+
+/// POST /files/{id}/share
+///
+/// Mints a signed, time-limited share link for a stored file
+pub fn share_create(
+    secret_db: Arc<Mutex<SecretDb>>,
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("files" / String / "share"))
+        .and(with_node_component(secret_db))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and(warp::body::json())
+        .and_then(move |file_name, sec_db, sig_db, pp, share_data| {
+            handle_share_create(file_name, share_data, sec_db, sig_db, pp)
+        })
+        .with(post_cors())
+}
+
+/// GET /s/{token}
+///
+/// Streams the decrypted file behind a share link
+pub fn share_download(
+    secret_db: Arc<Mutex<SecretDb>>,
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("s" / String))
+        .and(warp::addr::remote())
+        .and(with_node_component(secret_db))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(move |token, remote, sec_db, sig_db, pp| {
+            handle_share_download(token, remote, sec_db, sig_db, pp)
+        })
+        .with(get_cors())
+}
+
+/// POST /secrets
+///
+/// Stores a one-time secret and returns its retrieval token
+pub fn burn_create(
+    secret_db: Arc<Mutex<SecretDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path("secrets"))
+        .and(warp::path::end())
+        .and(with_node_component(secret_db))
+        .and(with_node_component(passphrase))
+        .and(warp::body::content_length_limit(MAX_BURN_SECRET_LEN))
+        .and(warp::body::json())
+        .and_then(move |db, pp, secret_data| handle_burn_create(secret_data, db, pp))
+        .with(post_cors())
+}
+
+/// GET /secrets/{token}
+///
+/// Reveals a one-time secret and destroys it
+pub fn burn_read(
+    secret_db: Arc<Mutex<SecretDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("secrets" / String))
+        .and(warp::header::optional::<String>("x-secret-passphrase"))
+        .and(with_node_component(secret_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_burn_read)
+        .with(get_cors())
+}
+
+/// POST /files/{id}/hold
+///
+/// Places or lifts a legal hold on a stored file
+pub fn legal_hold(
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("files" / String / "hold"))
+        .and(warp::body::json())
+        .and(with_node_component(secret_db))
+        .and_then(handle_legal_hold)
+        .with(post_cors())
+}
+
+/// POST /files/{id}/sign
+///
+/// Signs the digest of a stored file and keeps the signature with the file
+pub fn file_sign(
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("files" / String / "sign"))
+        .and(warp::body::json())
+        .and(with_node_component(secret_db))
+        .and(with_node_component(signature_db))
+        .and(with_node_component(log_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_file_sign)
+        .with(post_cors())
+}
+
+/// GET /files/{id}/signature
+///
+/// Gets the stored signature of a file
+pub fn file_signature(
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("files" / String / "signature"))
+        .and(warp::query())
+        .and(with_node_component(secret_db))
+        .and_then(handle_file_signature)
+        .with(get_cors())
+}
+
+/// POST /files/{id}/verify
+///
+/// Re-hashes a stored file and verifies its signature
+pub fn file_verify(
+    secret_db: Arc<Mutex<SecretDb>>,
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("files" / String / "verify"))
+        .and(warp::body::json())
+        .and(with_node_component(secret_db))
+        .and(with_node_component(signature_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_file_verify)
+        .with(post_cors())
+}
+
+/// DELETE /files/{id}
+///
+/// Crypto-shreds a stored file, unless it is under legal hold
+pub fn delete_file(
+    secret_db: Arc<Mutex<SecretDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::delete()
+        .and(warp::path!("files" / String))
+        .and(with_node_component(secret_db))
+        .and_then(handle_delete_file)
+        .with(delete_cors())
+}
+
+/// POST /keys
+///
+/// Creates a new signing keypair
+pub fn key_create(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path("keys"))
+        .and(warp::path::end())
+        .and(with_node_component(sig_db))
+        .and(warp::body::json())
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_create)
+        .with(post_cors())
+}
+
+/// POST /keys/{id}/import
+///
+/// Imports an existing Ed25519, P-256 or P-384 PKCS8 private key as a signing keypair
+pub fn key_import(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / String / "import"))
+        .and(warp::body::json())
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_import)
+        .with(post_cors())
+}
+
+/// POST /keys/{id}/rotate
+///
+/// Adds a new version to a signing keypair
+pub fn key_rotate(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / String / "rotate"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_rotate)
+        .with(post_cors())
+}
+
+/// POST /keys/{id}/versions/{version}/retire
+///
+/// Retires an older version of a signing keypair
+pub fn key_retire(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / String / "versions" / u32 / "retire"))
+        .and(with_node_component(sig_db))
+        .and_then(handle_key_retire)
+        .with(post_cors())
+}
+
+/// GET /keys/{id}
+///
+/// Gets the metadata and public key of a signing keypair
+pub fn key_get(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("keys" / String))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_get)
+        .with(get_cors())
+}
+
+/// GET /keys/{id}/derivation
+///
+/// Reproduces the public keys of a derived signing keypair from the master seed
+pub fn key_derivation(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("keys" / String / "derivation"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_derivation)
+        .with(get_cors())
+}
+
+/// POST /keys/seed
+///
+/// Restores the master seed that signing keys are derived from
+pub fn master_seed_import(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / "seed"))
+        .and(warp::body::json())
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_master_seed_import)
+        .with(post_cors())
+}
+
+/// GET /keys/{id}/public
+///
+/// Exports the public key of a signing keypair in hex, base64, PEM, JWK or OpenSSH format
+pub fn key_public(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("keys" / String / "public"))
+        .and(warp::query())
+        .and(warp::header::optional::<String>("accept"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_key_public)
+        .with(get_cors())
+}
+
+/// GET /.well-known/jwks.json
+///
+/// Publishes the public keys of all enabled signing keypairs
+pub fn jwks(
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!(".well-known" / "jwks.json"))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_jwks)
+        .with(get_cors())
+}
+
+/// DELETE /keys/{id}
+///
+/// Deletes a signing keypair
+pub fn key_delete(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::delete()
+        .and(warp::path!("keys" / String))
+        .and(with_node_component(sig_db))
+        .and_then(handle_key_delete)
+        .with(delete_cors())
+}
+
+/// POST /keys/{id}/disable
+///
+/// Disables a signing keypair so that it can no longer sign
+pub fn key_disable(
+    sig_db: Arc<Mutex<SignatureDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("keys" / String / "disable"))
+        .and(with_node_component(sig_db))
+        .and_then(handle_key_disable)
+        .with(post_cors())
+}
+
+/// POST /timestamp
+///
+/// Issues a signed timestamp token over a digest
+pub fn timestamp(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    log_db: Arc<Mutex<LogDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("timestamp"))
+        .and(warp::body::json())
+        .and(with_node_component(signature_db))
+        .and(with_node_component(log_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_timestamp)
+        .with(post_cors())
+}
+
+/// POST /timestamp/verify
+///
+/// Verifies a timestamp token
+pub fn timestamp_verify(
+    signature_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("timestamp" / "verify"))
+        .and(warp::body::json())
+        .and(with_node_component(signature_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_timestamp_verify)
+        .with(post_cors())
+}
+
+/// GET /log/sth
+///
+/// Gets the signed tree head of the transparency log
+pub fn log_tree_head(
+    log_db: Arc<Mutex<LogDb>>,
+    sig_db: Arc<Mutex<SignatureDb>>,
+    passphrase: String,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("log" / "sth"))
+        .and(with_node_component(log_db))
+        .and(with_node_component(sig_db))
+        .and(with_node_component(passphrase))
+        .and_then(handle_log_tree_head)
+        .with(get_cors())
+}
+
+/// GET /log/entries/{index}
+///
+/// Gets an entry of the transparency log
+pub fn log_entry(
+    log_db: Arc<Mutex<LogDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("log" / "entries" / u64))
+        .and(with_node_component(log_db))
+        .and_then(handle_log_entry)
+        .with(get_cors())
+}
+
+/// GET /log/proof/inclusion
+///
+/// Gets the inclusion proof of a transparency log entry
+pub fn log_inclusion_proof(
+    log_db: Arc<Mutex<LogDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("log" / "proof" / "inclusion"))
+        .and(warp::query())
+        .and(with_node_component(log_db))
+        .and_then(handle_log_inclusion_proof)
+        .with(get_cors())
+}
+
+/// GET /log/proof/consistency
+///
+/// Gets the consistency proof between two transparency log tree heads
+pub fn log_consistency_proof(
+    log_db: Arc<Mutex<LogDb>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("log" / "proof" / "consistency"))
+        .and(warp::query())
+        .and(with_node_component(log_db))
+        .and_then(handle_log_consistency_proof)
+        .with(get_cors())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::interfaces::{FileSignPayload, SharePayload};
+    use crate::api::utils::unix_timestamp;
+    use crate::crypto::generate_random;
+    use crate::crypto::secretbox_chacha20_poly1305::{seal, Key, Nonce};
+    use crate::crypto::sign_ed25519;
+    use crate::db::constants::INLINE_THRESHOLD;
+    use crate::db::secret_db::{CiphertextFormat, Compression};
+    use std::path::{Path, PathBuf};
+    use warp::http::StatusCode;
+
+    const PASSPHRASE: &str = "test";
+
+    /// Creates an empty directory for the database and uploads of a single test
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "freemason-test-{}",
+            hex::encode(generate_random::<8>())
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Opens a secret database inside a test directory
+    ///
+    /// ### Arguments
+    ///
+    /// * `dir` - Test directory
+    fn secret_db(dir: &Path) -> Arc<Mutex<SecretDb>> {
+        let url = dir.join("secret").to_string_lossy().to_string();
+        Arc::new(Mutex::new(SecretDb::new(url)))
+    }
+
+    /// Opens a signature database inside a test directory
+    ///
+    /// ### Arguments
+    ///
+    /// * `dir` - Test directory
+    fn signature_db(dir: &Path) -> Arc<Mutex<SignatureDb>> {
+        let url = dir.join("signatures").to_string_lossy().to_string();
+        Arc::new(Mutex::new(SignatureDb::new(url)))
+    }
+
+    /// Opens a transparency log database inside a test directory
+    ///
+    /// ### Arguments
+    ///
+    /// * `dir` - Test directory
+    fn log_db(dir: &Path) -> Arc<Mutex<LogDb>> {
+        let url = dir.join("transparency_log").to_string_lossy().to_string();
+        Arc::new(Mutex::new(LogDb::new(url)))
+    }
+
+    /// Uploads one chunk through `POST /upload` and returns the response status
+    ///
+    /// ### Arguments
+    ///
+    /// * `sec_db` - Secret database
+    /// * `query` - Chunk metadata query string
+    /// * `chunk` - Chunk of byte data
+    async fn upload_chunk(sec_db: &Arc<Mutex<SecretDb>>, query: &str, chunk: &[u8]) -> StatusCode {
+        warp::test::request()
+            .method("POST")
+            .path(&format!("/upload?timestamp=0&{}", query))
+            .body(chunk)
+            .reply(&upload_raw(sec_db.clone(), PASSPHRASE.to_string()))
+            .await
+            .status()
+    }
+
+    /// Downloads a whole file through `POST /download`, one chunk at a time
+    ///
+    /// ### Arguments
+    ///
+    /// * `sec_db` - Secret database
+    /// * `file_name` - Name of the file
+    async fn download_file(sec_db: &Arc<Mutex<SecretDb>>, file_name: &str) -> Vec<u8> {
+        let route = download(sec_db.clone(), PASSPHRASE.to_string());
+        let mut data = Vec::new();
+        let mut offset = 0;
+        loop {
+            let response = warp::test::request()
+                .method("POST")
+                .path("/download")
+                .json(&serde_json::json!({ "offset": offset, "file_name": file_name }))
+                .reply(&route)
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            data.extend(serde_json::from_value::<Vec<u8>>(body["data"].clone()).unwrap());
+            if body["isLastChunk"].as_bool().unwrap() {
+                return data;
+            }
+            offset = body["nextOffset"].as_u64().unwrap();
+        }
+    }
+
+    /// Posts a JSON body to a route and returns the response status and JSON body
+    ///
+    /// ### Arguments
+    ///
+    /// * `route` - Route to send the request to
+    /// * `path` - Request path
+    /// * `body` - JSON body
+    async fn post_json<F>(
+        route: &F,
+        path: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value)
+    where
+        F: Filter + 'static,
+        F::Extract: Reply + Send,
+    {
+        let response = warp::test::request()
+            .method("POST")
+            .path(path)
+            .json(&body)
+            .reply(route)
+            .await;
+        let body = serde_json::from_slice(response.body()).unwrap_or_default();
+        (response.status(), body)
+    }
+
+    #[tokio::test]
+    async fn upload_then_download() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("hello.txt").to_string_lossy().to_string();
+
+        let query = format!("file_name={}&chunk_number=0&total_chunks=1", file_name);
+        let status = upload_chunk(&sec_db, &query, b"hello world").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(download_file(&sec_db, &file_name).await, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn multi_chunk_upload_then_download() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("chunks.bin").to_string_lossy().to_string();
+        let chunks: [&[u8]; 3] = [b"first chunk, ", b"second chunk, ", b"third chunk"];
+
+        for (chunk_number, chunk) in chunks.iter().enumerate() {
+            let query = format!(
+                "file_name={}&chunk_number={}&total_chunks=3",
+                file_name, chunk_number
+            );
+            assert_eq!(upload_chunk(&sec_db, &query, chunk).await, StatusCode::OK);
+        }
+
+        assert_eq!(download_file(&sec_db, &file_name).await, chunks.concat());
+    }
+
+    #[tokio::test]
+    async fn first_chunk_replaces_earlier_upload() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("replaced.bin").to_string_lossy().to_string();
+
+        for chunk_number in 0..2 {
+            let query = format!(
+                "file_name={}&chunk_number={}&total_chunks=2",
+                file_name, chunk_number
+            );
+            assert_eq!(upload_chunk(&sec_db, &query, b"old").await, StatusCode::OK);
+        }
+        let query = format!("file_name={}&chunk_number=0&total_chunks=1", file_name);
+        assert_eq!(upload_chunk(&sec_db, &query, b"new").await, StatusCode::OK);
+
+        assert_eq!(download_file(&sec_db, &file_name).await, b"new");
+    }
+
+    #[tokio::test]
+    async fn share_link_streams_multi_chunk_file() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let sig_db = signature_db(&dir);
+        let file_name = dir.join("shared.bin").to_string_lossy().to_string();
+        let chunks: [&[u8]; 2] = [b"shared ", b"file"];
+
+        for (chunk_number, chunk) in chunks.iter().enumerate() {
+            let query = format!(
+                "file_name={}&chunk_number={}&total_chunks=2",
+                file_name, chunk_number
+            );
+            assert_eq!(upload_chunk(&sec_db, &query, chunk).await, StatusCode::OK);
+        }
+
+        let share_payload = SharePayload {
+            expires_in: u64::MAX,
+            max_downloads: None,
+            ip: None,
+        };
+        let response = handle_share_create(
+            file_name.clone(),
+            share_payload,
+            sec_db.clone(),
+            sig_db.clone(),
+            PASSPHRASE.to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let share_payload = SharePayload {
+            expires_in: 60,
+            max_downloads: None,
+            ip: None,
+        };
+        let response = handle_share_create(
+            file_name,
+            share_payload,
+            sec_db.clone(),
+            sig_db.clone(),
+            PASSPHRASE.to_string(),
+        )
+        .await
+        .unwrap();
+        let body = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let token = body["token"].as_str().unwrap().to_string();
+        let response = handle_share_download(token, None, sec_db, sig_db, PASSPHRASE.to_string())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let data = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(data, chunks.concat());
+    }
+
+    #[tokio::test]
+    async fn unframed_file_downloads() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("unframed.bin").to_string_lossy().to_string();
+
+        // Stored the way uploads were before chunks were length-prefixed
+        let (key, nonce) = (Key::new(), Nonce::new());
+        let sealed = seal(b"stored before framing".to_vec(), &nonce, &key).unwrap();
+        std::fs::write(&file_name, sealed).unwrap();
+
+        let sec_db_lock = sec_db.lock().await;
+        let mut sec_entry = sec_db_lock.create_secret_entry(
+            &file_name,
+            PASSPHRASE,
+            1,
+            (key, nonce),
+            None,
+            (Compression::None, false),
+        );
+        sec_entry.format = CiphertextFormat::Unframed;
+        sec_db_lock.insert_secret(sec_entry).await.unwrap();
+        drop(sec_db_lock);
+
+        assert_eq!(
+            download_file(&sec_db, &file_name).await,
+            b"stored before framing"
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_expiry() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let expired = dir.join("expired.txt").to_string_lossy().to_string();
+        let kept = dir.join("kept.bin").to_string_lossy().to_string();
+
+        let query = format!(
+            "file_name={}&chunk_number=0&total_chunks=1&expires_at=1",
+            expired
+        );
+        assert_eq!(upload_chunk(&sec_db, &query, b"gone").await, StatusCode::OK);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/download")
+            .json(&serde_json::json!({ "offset": 0, "file_name": expired }))
+            .reply(&download(sec_db.clone(), PASSPHRASE.to_string()))
+            .await;
+        assert_eq!(response.status(), StatusCode::GONE);
+
+        // Only the first chunk carries the ttl, later chunks keep its expiry
+        let query = format!("file_name={}&chunk_number=0&total_chunks=2&ttl=3600", kept);
+        assert_eq!(
+            upload_chunk(&sec_db, &query, b"kept ").await,
+            StatusCode::OK
+        );
+        let query = format!("file_name={}&chunk_number=1&total_chunks=2", kept);
+        assert_eq!(upload_chunk(&sec_db, &query, b"file").await, StatusCode::OK);
+
+        let sec_entry = sec_db.lock().await.get_secret_entry(&kept).await.unwrap();
+        let expires_at = sec_entry.unwrap().expires_at.unwrap();
+        assert!(expires_at > unix_timestamp() && expires_at <= unix_timestamp() + 3600);
+        assert_eq!(download_file(&sec_db, &kept).await, b"kept file");
+
+        // A ttl past the end of time is refused before the file is touched
+        let query = format!(
+            "file_name={}&chunk_number=0&total_chunks=1&ttl={}",
+            kept,
+            u64::MAX
+        );
+        assert_eq!(
+            upload_chunk(&sec_db, &query, b"new").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(download_file(&sec_db, &kept).await, b"kept file");
+    }
+
+    #[tokio::test]
+    async fn upload_compression() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("compressed.log").to_string_lossy().to_string();
+        let chunks = [vec![b'a'; 200 * 1024], vec![b'b'; 200 * 1024]];
+
+        // Later chunks inherit the compression of the first
+        let query = format!(
+            "file_name={}&chunk_number=0&total_chunks=2&compression=zstd",
+            file_name
+        );
+        assert_eq!(
+            upload_chunk(&sec_db, &query, &chunks[0]).await,
+            StatusCode::OK
+        );
+        let query = format!("file_name={}&chunk_number=1&total_chunks=2", file_name);
+        assert_eq!(
+            upload_chunk(&sec_db, &query, &chunks[1]).await,
+            StatusCode::OK
+        );
+
+        let query = format!(
+            "file_name={}&chunk_number=1&total_chunks=2&compression=none",
+            file_name
+        );
+        let status = upload_chunk(&sec_db, &query, &chunks[1]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let sec_entry = sec_db.lock().await.get_secret_entry(&file_name).await;
+        assert_eq!(sec_entry.unwrap().unwrap().compression, Compression::Zstd);
+        assert!(std::fs::metadata(&file_name).unwrap().len() < 10 * 1024);
+        assert_eq!(download_file(&sec_db, &file_name).await, chunks.concat());
+    }
+
+    #[tokio::test]
+    async fn upload_inline_storage() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let file_name = dir.join("inline.bin").to_string_lossy().to_string();
+        let query = format!("file_name={}&chunk_number=0&total_chunks=1", file_name);
+        let small = vec![1; 1024];
+        let large = vec![2; INLINE_THRESHOLD + 1];
+
+        assert_eq!(upload_chunk(&sec_db, &query, &small).await, StatusCode::OK);
+        let sec_entry = sec_db.lock().await.get_secret_entry(&file_name).await;
+        assert!(sec_entry.unwrap().unwrap().inline);
+        assert!(!Path::new(&file_name).exists());
+        assert_eq!(download_file(&sec_db, &file_name).await, small);
+
+        assert_eq!(upload_chunk(&sec_db, &query, &large).await, StatusCode::OK);
+        let sec_entry = sec_db.lock().await.get_secret_entry(&file_name).await;
+        assert!(!sec_entry.unwrap().unwrap().inline);
+        assert!(Path::new(&file_name).exists());
+        assert_eq!(download_file(&sec_db, &file_name).await, large);
+
+        // Switching back to inline storage drops the file
+        assert_eq!(upload_chunk(&sec_db, &query, &small).await, StatusCode::OK);
+        assert!(!Path::new(&file_name).exists());
+        assert_eq!(download_file(&sec_db, &file_name).await, small);
+    }
+
+    #[tokio::test]
+    async fn jwt_verify_rejects_other_token_types() {
+        let dir = test_dir();
+        let sig_db = signature_db(&dir);
+        let log_db = log_db(&dir);
+        let passphrase = PASSPHRASE.to_string();
+
+        let (status, _) = post_json(
+            &key_create(sig_db.clone(), passphrase.clone()),
+            "/keys",
+            serde_json::json!({ "id": "jwt-key" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let issue = jwt_issue(sig_db.clone(), log_db.clone(), passphrase.clone());
+        let (status, _) = post_json(
+            &issue,
+            "/keys/jwt-key/jwt",
+            serde_json::json!({ "claims": {}, "expires_in": u64::MAX }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = post_json(
+            &issue,
+            "/keys/jwt-key/jwt",
+            serde_json::json!({ "claims": { "sub": "client" } }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let jwt = body["token"].clone();
+
+        let (status, body) = post_json(
+            &timestamp(sig_db.clone(), log_db, passphrase.clone()),
+            "/timestamp",
+            serde_json::json!({ "digest": hex::encode([0; 32]) }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let timestamp_token = body["token"].clone();
+
+        let verify = jwt_verify(sig_db, passphrase);
+        let (_, body) =
+            post_json(&verify, "/jwt/verify", serde_json::json!({ "token": jwt })).await;
+        assert_eq!(body["valid"], true);
+        let (_, body) = post_json(
+            &verify,
+            "/jwt/verify",
+            serde_json::json!({ "token": timestamp_token }),
+        )
+        .await;
+        assert_eq!(body["valid"], false);
+    }
+
+    #[tokio::test]
+    async fn policy_counts_each_key_once() {
+        let dir = test_dir();
+        let sig_db = signature_db(&dir);
+        let (alice_pk, alice_sk) = sign_ed25519::gen_keypair();
+        let (bob_pk, bob_sk) = sign_ed25519::gen_keypair();
+        let signer = |name: &str, pk: &sign_ed25519::PublicKey| serde_json::json!({ "name": name, "public_key": hex::encode(pk) });
+
+        let create = policy_create(sig_db.clone());
+        let (status, _) = post_json(
+            &create,
+            "/policies",
+            serde_json::json!({
+                "name": "shared",
+                "threshold": 2,
+                "signers": [signer("alice", &alice_pk), signer("mallory", &alice_pk)]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = post_json(
+            &create,
+            "/policies",
+            serde_json::json!({
+                "name": "pair",
+                "threshold": 2,
+                "signers": [signer("alice", &alice_pk), signer("bob", &bob_pk)]
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let message = b"policy message";
+        let alice_sig = hex::encode(sign_ed25519::sign_detached(message, &alice_sk));
+        let bob_sig = hex::encode(sign_ed25519::sign_detached(message, &bob_sk));
+        let verify = verify_policy(sig_db, PASSPHRASE.to_string());
+        let check = |signatures: Vec<&String>| {
+            let signatures: Vec<_> = signatures
+                .into_iter()
+                .map(|signature| serde_json::json!({ "signature": signature }))
+                .collect();
+            serde_json::json!({
+                "policy": "pair",
+                "message": "policy message",
+                "signatures": signatures
+            })
+        };
+
+        let (_, body) = post_json(
+            &verify,
+            "/verify/policy",
+            check(vec![&alice_sig, &alice_sig]),
+        )
+        .await;
+        assert_eq!(body["satisfied"], false);
+        assert_eq!(body["valid_signers"], serde_json::json!(["alice"]));
+
+        let (_, body) =
+            post_json(&verify, "/verify/policy", check(vec![&alice_sig, &bob_sig])).await;
+        assert_eq!(body["satisfied"], true);
+    }
+
+    #[tokio::test]
+    async fn jwks_keeps_superseded_and_retired_versions() {
+        let dir = test_dir();
+        let sig_db = signature_db(&dir);
+        let passphrase = PASSPHRASE.to_string();
+
+        let (status, _) = post_json(
+            &key_create(sig_db.clone(), passphrase.clone()),
+            "/keys",
+            serde_json::json!({ "id": "rotated" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let rotate = key_rotate(sig_db.clone(), passphrase.clone());
+        for _ in 0..2 {
+            let (status, _) =
+                post_json(&rotate, "/keys/rotated/rotate", serde_json::json!({})).await;
+            assert!(status.is_success());
+        }
+        let (status, _) = post_json(
+            &key_retire(sig_db.clone()),
+            "/keys/rotated/versions/1/retire",
+            serde_json::json!({}),
+        )
+        .await;
+        assert!(status.is_success());
+
+        let response = warp::test::request()
+            .path("/.well-known/jwks.json")
+            .reply(&jwks(sig_db, passphrase))
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["keys"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn issued_tokens_and_file_signatures_are_logged() {
+        let dir = test_dir();
+        let sec_db = secret_db(&dir);
+        let sig_db = signature_db(&dir);
+        let log_db = log_db(&dir);
+        let passphrase = PASSPHRASE.to_string();
+
+        let (status, _) = post_json(
+            &key_create(sig_db.clone(), passphrase.clone()),
+            "/keys",
+            serde_json::json!({ "id": "logged" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (_, body) = post_json(
+            &jwt_issue(sig_db.clone(), log_db.clone(), passphrase.clone()),
+            "/keys/logged/jwt",
+            serde_json::json!({ "claims": {} }),
+        )
+        .await;
+        assert_eq!(body["log_index"], 0);
+
+        let (_, body) = post_json(
+            &timestamp(sig_db.clone(), log_db.clone(), passphrase.clone()),
+            "/timestamp",
+            serde_json::json!({ "digest": hex::encode([0; 32]) }),
+        )
+        .await;
+        assert_eq!(body["log_index"], 1);
+
+        let file_name = dir.join("signed.txt").to_string_lossy().to_string();
+        let query = format!("file_name={}&chunk_number=0&total_chunks=1", file_name);
+        assert_eq!(
+            upload_chunk(&sec_db, &query, b"signed").await,
+            StatusCode::OK
+        );
+        let sign_payload = FileSignPayload {
+            key_id: "logged".to_string(),
+            signature_encoding: None,
+            signature_format: None,
+        };
+        let response =
+            handle_file_sign(file_name, sign_payload, sec_db, sig_db, log_db, passphrase)
+                .await
+                .unwrap();
+        let body = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["log_index"], 2);
+    }
+
+    #[tokio::test]
+    async fn burn_secret_ttl_must_fit() {
+        let dir = test_dir();
+        let route = burn_create(secret_db(&dir), PASSPHRASE.to_string());
+
+        let (status, _) = post_json(
+            &route,
+            "/secrets",
+            serde_json::json!({ "secret": "burn", "ttl": u64::MAX }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = post_json(
+            &route,
+            "/secrets",
+            serde_json::json!({ "secret": "burn", "ttl": 60 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["expires_at"].as_u64().unwrap() > unix_timestamp());
+    }
+}
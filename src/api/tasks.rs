@@ -0,0 +1,52 @@
+use super::utils::unix_timestamp;
+use crate::db::constants::{KEY_SWEEP_INTERVAL, RETENTION_SWEEP_INTERVAL};
+use crate::db::secret_db::SecretDb;
+use crate::db::sign_db::SignatureDb;
+use futures::lock::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Periodically crypto-shreds expired secret entries
+///
+/// ### Arguments
+///
+/// * `secret_db` - Secret database
+pub async fn retention_sweeper(secret_db: Arc<Mutex<SecretDb>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(RETENTION_SWEEP_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        match secret_db.lock().await.purge_expired(unix_timestamp()).await {
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to shred expired secret entries: {}", e.message),
+        }
+    }
+}
+
+/// Periodically deletes signing keypairs whose versions have all expired and are
+/// past the verification grace period
+///
+/// ### Arguments
+///
+/// * `signature_db` - Signature database
+pub async fn key_expiry_sweeper(signature_db: Arc<Mutex<SignatureDb>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(KEY_SWEEP_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        match signature_db
+            .lock()
+            .await
+            .purge_expired_keys(unix_timestamp())
+            .await
+        {
+            Ok(purged) if !purged.is_empty() => {
+                log::info!("Deleted {} expired signing keys", purged.len())
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to delete expired signing keys: {}", e.message),
+        }
+    }
+}
@@ -1,6 +1,20 @@
+use super::interfaces::{JwtHeader, ShareToken};
+use crate::crypto::secretbox_chacha20_poly1305::{open, Key, Nonce};
+use crate::crypto::sha3_256::{Digest, Output, Sha3_256};
+use crate::crypto::sign_ed25519::Signature;
+use crate::db::constants::{SEALED_CHUNK_PREFIX_LEN, UNFRAMED_CHUNK_LEN};
+use crate::db::secret_db::{CiphertextFormat, Compression};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::{Stream, StreamExt};
 use std::convert::Infallible;
+use std::io::{Error, ErrorKind, Read};
+use std::time::{SystemTime, UNIX_EPOCH};
 use warp::Filter;
 
+/// Claims of a JWT
+pub type JwtClaims = serde_json::Map<String, serde_json::Value>;
+
 /// Easy and simple POST CORS
 pub fn post_cors() -> warp::cors::Builder {
     warp::cors()
@@ -39,6 +53,25 @@ pub fn get_cors() -> warp::cors::Builder {
         .allow_methods(vec!["GET", "OPTIONS"])
 }
 
+/// Easy and simple DELETE CORS
+pub fn delete_cors() -> warp::cors::Builder {
+    warp::cors()
+        .allow_any_origin()
+        .allow_headers(vec![
+            "Accept",
+            "User-Agent",
+            "Sec-Fetch-Mode",
+            "Referer",
+            "Origin",
+            "Access-Control-Request-Method",
+            "Access-Control-Request-Headers",
+            "Access-Control-Allow-Origin",
+            "Access-Control-Allow-Headers",
+            "Content-Type",
+        ])
+        .allow_methods(vec!["DELETE", "OPTIONS"])
+}
+
 /// Clone component/struct to use in route
 ///
 /// ### Arguments
@@ -48,4 +81,218 @@ pub fn with_node_component<T: Clone + Send>(
     comp: T,
 ) -> impl Filter<Extract = (T,), Error = Infallible> + Clone {
     warp::any().map(move || comp.clone())
-}
\ No newline at end of file
+}
+
+/// Current UNIX time in seconds
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Combines the server passphrase with an optional caller supplied one
+///
+/// ### Arguments
+///
+/// * `passphrase` - Server passphrase
+/// * `user_passphrase` - Optional caller passphrase
+pub fn combine_passphrase(passphrase: &str, user_passphrase: Option<&str>) -> String {
+    match user_passphrase {
+        Some(user_passphrase) => format!("{}:{}", passphrase, user_passphrase),
+        None => passphrase.to_string(),
+    }
+}
+
+/// Encodes the claims of a share link as the signable part of its token
+///
+/// ### Arguments
+///
+/// * `claims` - Share link claims
+pub fn encode_share_claims(claims: &ShareToken) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap())
+}
+
+/// Decodes a share link token into its signed part, claims and signature
+///
+/// ### Arguments
+///
+/// * `token` - Share link token in the form `<claims>.<signature>`
+pub fn decode_share_token(token: &str) -> Option<(String, ShareToken, Signature)> {
+    let (encoded_claims, encoded_sig) = token.split_once('.')?;
+    let claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded_claims).ok()?).ok()?;
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(encoded_sig).ok()?)?;
+
+    Some((encoded_claims.to_string(), claims, signature))
+}
+
+/// Encodes a JSON value as a base64url JWS segment
+///
+/// ### Arguments
+///
+/// * `value` - Value to encode
+pub fn encode_jwt_segment<T: serde::Serialize>(value: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(value).unwrap())
+}
+
+/// Decodes a compact JWS into its signing input, header, claims and signature
+///
+/// ### Arguments
+///
+/// * `token` - Token in the form `<header>.<claims>.<signature>`
+pub fn decode_jwt(token: &str) -> Option<(String, JwtHeader, JwtClaims, Vec<u8>)> {
+    let (signing_input, encoded_sig) = token.trim().rsplit_once('.')?;
+    let (encoded_header, encoded_claims) = signing_input.split_once('.')?;
+    let header = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded_header).ok()?).ok()?;
+    let claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded_claims).ok()?).ok()?;
+    let signature = URL_SAFE_NO_PAD.decode(encoded_sig).ok()?;
+
+    Some((signing_input.to_string(), header, claims, signature))
+}
+
+/// Frames a sealed chunk for storage by prefixing it with its length. Compressed
+/// chunks seal to different lengths, so chunks cannot be found by a fixed size
+///
+/// ### Arguments
+///
+/// * `sealed_chunk` - Sealed chunk
+pub fn frame_sealed_chunk(sealed_chunk: Vec<u8>) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(SEALED_CHUNK_PREFIX_LEN + sealed_chunk.len());
+    framed.extend((sealed_chunk.len() as u32).to_be_bytes());
+    framed.extend(sealed_chunk);
+    framed
+}
+
+/// Reads the next sealed chunk of a ciphertext stored in the given format, along
+/// with the number of stored bytes it took up. Returns None at the end of the
+/// ciphertext
+///
+/// ### Arguments
+///
+/// * `reader` - Reader positioned at the start of a stored chunk
+/// * `format` - Layout of the stored ciphertext
+pub fn read_sealed_chunk(
+    reader: &mut dyn Read,
+    format: CiphertextFormat,
+) -> Result<Option<(Vec<u8>, u64)>, Error> {
+    // Unframed chunks were stored at their full sealed size, bar the last one
+    if format == CiphertextFormat::Unframed {
+        let mut sealed_chunk = Vec::new();
+        reader
+            .take(UNFRAMED_CHUNK_LEN as u64)
+            .read_to_end(&mut sealed_chunk)?;
+        let stored_len = sealed_chunk.len() as u64;
+        return Ok(Some((sealed_chunk, stored_len)).filter(|_| stored_len > 0));
+    }
+
+    let mut prefix = [0; SEALED_CHUNK_PREFIX_LEN];
+    match reader.read(&mut prefix[..1])? {
+        0 => return Ok(None),
+        _ => reader.read_exact(&mut prefix[1..])?,
+    }
+
+    // Read through `take` so a corrupt prefix cannot force a huge allocation
+    let len = u32::from_be_bytes(prefix) as u64;
+    let mut sealed_chunk = Vec::new();
+    reader.take(len).read_to_end(&mut sealed_chunk)?;
+    if sealed_chunk.len() as u64 != len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Truncated encrypted chunk",
+        ));
+    }
+
+    Ok(Some((sealed_chunk, SEALED_CHUNK_PREFIX_LEN as u64 + len)))
+}
+
+/// Nonce of the chunk stored at a byte offset of a file's ciphertext. Every chunk of
+/// a framed file is sealed with the file's key, so each is given a nonce of its own
+/// by mixing its offset into the file's nonce. Unframed chunks use the file's nonce
+///
+/// ### Arguments
+///
+/// * `nonce` - Nonce of the file
+/// * `offset` - Byte offset of the stored chunk in the ciphertext
+/// * `format` - Layout of the stored ciphertext
+pub fn chunk_nonce(nonce: &Nonce, offset: u64, format: CiphertextFormat) -> Nonce {
+    if format == CiphertextFormat::Unframed {
+        return *nonce;
+    }
+
+    let mut chunk_nonce = nonce.as_ref().to_vec();
+    let counter_start = chunk_nonce.len() - offset.to_be_bytes().len();
+    for (byte, offset_byte) in chunk_nonce[counter_start..]
+        .iter_mut()
+        .zip(offset.to_be_bytes())
+    {
+        *byte ^= offset_byte;
+    }
+
+    Nonce::from_slice(&chunk_nonce).unwrap()
+}
+
+/// Turns an encrypted ciphertext reader into a stream of decrypted chunks
+///
+/// ### Arguments
+///
+/// * `reader` - Reader over the sealed chunks
+/// * `key` - Key the chunks were sealed with
+/// * `nonce` - Nonce the chunk nonces are derived from
+/// * `compression` - Compression applied to each chunk before sealing
+/// * `format` - Layout of the stored ciphertext
+pub fn decrypted_stream(
+    reader: Box<dyn Read + Send>,
+    key: Key,
+    nonce: Nonce,
+    compression: Compression,
+    format: CiphertextFormat,
+) -> impl Stream<Item = Result<bytes::Bytes, Error>> {
+    futures::stream::unfold((reader, 0), move |(mut reader, offset)| {
+        let key = key.clone();
+        async move {
+            let (encrypted_chunk, stored_len) = match read_sealed_chunk(&mut reader, format) {
+                Ok(Some(read)) => read,
+                Ok(None) => return None,
+                Err(e) => return Some((Err(e), (reader, offset))),
+            };
+
+            let nonce = chunk_nonce(&nonce, offset, format);
+            let chunk = match open(encrypted_chunk, &nonce, &key)
+                .and_then(|data| compression.decompress(data))
+            {
+                Some(data) => Ok(bytes::Bytes::from(data)),
+                None => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Failed to decrypt chunk",
+                )),
+            };
+            Some((chunk, (reader, offset + stored_len)))
+        }
+    })
+}
+
+/// Hashes the decrypted plaintext of a ciphertext reader with SHA3-256, one chunk
+/// at a time
+///
+/// ### Arguments
+///
+/// * `reader` - Reader over the sealed chunks
+/// * `key` - Key the chunks were sealed with
+/// * `nonce` - Nonce the chunk nonces are derived from
+/// * `compression` - Compression applied to each chunk before sealing
+/// * `format` - Layout of the stored ciphertext
+pub async fn decrypted_digest(
+    reader: Box<dyn Read + Send>,
+    key: Key,
+    nonce: Nonce,
+    compression: Compression,
+    format: CiphertextFormat,
+) -> Result<Output<Sha3_256>, Error> {
+    let mut hasher = Sha3_256::new();
+    let mut stream = Box::pin(decrypted_stream(reader, key, nonce, compression, format));
+    while let Some(chunk) = stream.next().await {
+        hasher.update(chunk?);
+    }
+
+    Ok(hasher.finalize())
+}
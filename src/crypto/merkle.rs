@@ -0,0 +1,425 @@
+use crate::crypto::sha3_256::{Digest, Sha3_256};
+
+/// Hash of a Merkle tree node
+pub type Hash = [u8; 32];
+
+/// Looks up the stored hash of a perfect subtree by its level (0 for leaves) and
+/// its index among the subtrees of that level
+pub trait NodeStore {
+    fn node(&self, level: u8, index: u64) -> Option<Hash>;
+}
+
+/// Hash of an empty tree
+pub fn empty_root() -> Hash {
+    Sha3_256::digest([]).into()
+}
+
+/// Hash of a leaf, domain separated from interior nodes as in RFC 6962
+///
+/// ### Arguments
+///
+/// * `leaf_input` - Data of the leaf
+pub fn leaf_hash(leaf_input: &[u8]) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x00]);
+    hasher.update(leaf_input);
+    hasher.finalize().into()
+}
+
+/// Hash of an interior node, domain separated from leaves as in RFC 6962
+///
+/// ### Arguments
+///
+/// * `left` - Hash of the left child
+/// * `right` - Hash of the right child
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha3_256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Hashes of the perfect subtrees completed by appending the leaf at `index`, from
+/// the leaf itself upwards, as `(level, index, hash)`
+///
+/// ### Arguments
+///
+/// * `store` - Stored nodes of the tree before the append
+/// * `index` - Index of the new leaf
+/// * `leaf` - Hash of the new leaf
+pub fn append_nodes(
+    store: &impl NodeStore,
+    index: u64,
+    leaf: Hash,
+) -> Option<Vec<(u8, u64, Hash)>> {
+    let mut nodes = vec![(0, index, leaf)];
+    let (mut level, mut index, mut hash) = (0, index, leaf);
+    while index % 2 == 1 {
+        hash = node_hash(&store.node(level, index - 1)?, &hash);
+        level += 1;
+        index /= 2;
+        nodes.push((level, index, hash));
+    }
+
+    Some(nodes)
+}
+
+/// Root hash of the first `size` leaves, MTH(D[0:size]) in RFC 6962
+///
+/// ### Arguments
+///
+/// * `store` - Stored nodes of the tree
+/// * `size` - Number of leaves
+pub fn root_hash(store: &impl NodeStore, size: u64) -> Option<Hash> {
+    match size {
+        0 => Some(empty_root()),
+        size => subtree_hash(store, 0, size),
+    }
+}
+
+/// Audit path of the leaf at `index` in the tree of the first `size` leaves,
+/// PATH(index, D[0:size]) in RFC 6962
+///
+/// ### Arguments
+///
+/// * `store` - Stored nodes of the tree
+/// * `index` - Index of the leaf
+/// * `size` - Number of leaves
+pub fn inclusion_proof(store: &impl NodeStore, index: u64, size: u64) -> Option<Vec<Hash>> {
+    if index >= size {
+        return None;
+    }
+
+    let mut proof = Vec::new();
+    let (mut start, mut end) = (0, size);
+    while end - start > 1 {
+        let split = start + largest_power_of_two_below(end - start);
+        if index < split {
+            proof.push(subtree_hash(store, split, end)?);
+            end = split;
+        } else {
+            proof.push(subtree_hash(store, start, split)?);
+            start = split;
+        }
+    }
+
+    proof.reverse();
+    Some(proof)
+}
+
+/// Proof that the tree of the first `first` leaves is a prefix of the tree of the
+/// first `second` leaves, PROOF(first, D[0:second]) in RFC 6962
+///
+/// ### Arguments
+///
+/// * `store` - Stored nodes of the tree
+/// * `first` - Size of the older tree
+/// * `second` - Size of the newer tree
+pub fn consistency_proof(store: &impl NodeStore, first: u64, second: u64) -> Option<Vec<Hash>> {
+    if first == 0 || first > second {
+        return None;
+    }
+
+    let mut proof = Vec::new();
+    let (mut start, mut end, mut m) = (0, second, first);
+    let mut whole = true;
+    while m != end - start {
+        let k = largest_power_of_two_below(end - start);
+        if m <= k {
+            proof.push(subtree_hash(store, start + k, end)?);
+            end = start + k;
+        } else {
+            proof.push(subtree_hash(store, start, start + k)?);
+            start += k;
+            m -= k;
+            whole = false;
+        }
+    }
+    if !whole {
+        proof.push(subtree_hash(store, start, end)?);
+    }
+
+    proof.reverse();
+    Some(proof)
+}
+
+/// Hash of the leaves in `start..end`. Every range visited by the RFC 6962
+/// algorithms is either a perfect, aligned subtree, which is looked up, or splits
+/// into one and a smaller remainder
+fn subtree_hash(store: &impl NodeStore, start: u64, end: u64) -> Option<Hash> {
+    let size = end - start;
+    if size.is_power_of_two() {
+        let level = size.trailing_zeros();
+        return store.node(level as u8, start >> level);
+    }
+
+    let split = start + largest_power_of_two_below(size);
+    Some(node_hash(
+        &subtree_hash(store, start, split)?,
+        &subtree_hash(store, split, end)?,
+    ))
+}
+
+/// Largest power of two strictly less than `n`, for `n > 1`
+fn largest_power_of_two_below(n: u64) -> u64 {
+    1 << (63 - (n - 1).leading_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const MAX_LEAVES: u64 = 20;
+
+    impl NodeStore for HashMap<(u8, u64), Hash> {
+        fn node(&self, level: u8, index: u64) -> Option<Hash> {
+            self.get(&(level, index)).copied()
+        }
+    }
+
+    /// Leaf hashes of a test tree, and its nodes stored by appending them in order
+    fn test_tree(size: u64) -> (Vec<Hash>, HashMap<(u8, u64), Hash>) {
+        let leaves: Vec<Hash> = (0..size)
+            .map(|i| leaf_hash(format!("leaf {}", i).as_bytes()))
+            .collect();
+
+        let mut store = HashMap::new();
+        for (index, leaf) in leaves.iter().enumerate() {
+            for (level, index, hash) in append_nodes(&store, index as u64, *leaf).unwrap() {
+                store.insert((level, index), hash);
+            }
+        }
+
+        (leaves, store)
+    }
+
+    /// MTH(D[n]) from RFC 6962, section 2.1
+    fn reference_root(leaves: &[Hash]) -> Hash {
+        match leaves.len() {
+            0 => empty_root(),
+            1 => leaves[0],
+            n => {
+                let k = largest_power_of_two_below(n as u64) as usize;
+                node_hash(&reference_root(&leaves[..k]), &reference_root(&leaves[k..]))
+            }
+        }
+    }
+
+    /// PATH(m, D[n]) from RFC 6962, section 2.1.1
+    fn reference_path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+        if leaves.len() <= 1 {
+            return Vec::new();
+        }
+
+        let k = largest_power_of_two_below(leaves.len() as u64) as usize;
+        let (mut path, sibling) = match m < k {
+            true => (
+                reference_path(m, &leaves[..k]),
+                reference_root(&leaves[k..]),
+            ),
+            false => (
+                reference_path(m - k, &leaves[k..]),
+                reference_root(&leaves[..k]),
+            ),
+        };
+        path.push(sibling);
+        path
+    }
+
+    /// PROOF(m, D[n]) from RFC 6962, section 2.1.2
+    fn reference_consistency(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+        fn subproof(m: usize, leaves: &[Hash], whole: bool) -> Vec<Hash> {
+            let n = leaves.len();
+            if m == n {
+                return match whole {
+                    true => Vec::new(),
+                    false => vec![reference_root(leaves)],
+                };
+            }
+
+            let k = largest_power_of_two_below(n as u64) as usize;
+            let (mut proof, sibling) = match m <= k {
+                true => (
+                    subproof(m, &leaves[..k], whole),
+                    reference_root(&leaves[k..]),
+                ),
+                false => (
+                    subproof(m - k, &leaves[k..], false),
+                    reference_root(&leaves[..k]),
+                ),
+            };
+            proof.push(sibling);
+            proof
+        }
+
+        subproof(m, leaves, true)
+    }
+
+    /// Inclusion proof verification from RFC 9162, section 2.1.3.2
+    fn verify_inclusion(index: u64, size: u64, leaf: Hash, proof: &[Hash], root: Hash) -> bool {
+        if index >= size {
+            return false;
+        }
+
+        let (mut fnode, mut snode, mut hash) = (index, size - 1, leaf);
+        for p in proof {
+            if snode == 0 {
+                return false;
+            }
+            if fnode & 1 == 1 || fnode == snode {
+                hash = node_hash(p, &hash);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, p);
+            }
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && hash == root
+    }
+
+    /// Consistency proof verification from RFC 9162, section 2.1.4.2
+    fn verify_consistency(
+        first: u64,
+        second: u64,
+        first_root: Hash,
+        second_root: Hash,
+        proof: &[Hash],
+    ) -> bool {
+        if first == second {
+            return proof.is_empty() && first_root == second_root;
+        }
+        if first == 0 || first > second {
+            return false;
+        }
+
+        let mut path = proof.to_vec();
+        if first.is_power_of_two() {
+            path.insert(0, first_root);
+        }
+        let (mut fnode, mut snode) = (first - 1, second - 1);
+        while fnode & 1 == 1 {
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        let (mut first_hash, mut second_hash) = match path.first() {
+            Some(hash) => (*hash, *hash),
+            None => return false,
+        };
+        for c in &path[1..] {
+            if snode == 0 {
+                return false;
+            }
+            if fnode & 1 == 1 || fnode == snode {
+                first_hash = node_hash(c, &first_hash);
+                second_hash = node_hash(c, &second_hash);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                second_hash = node_hash(&second_hash, c);
+            }
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && first_hash == first_root && second_hash == second_root
+    }
+
+    #[test]
+    fn root_hash_matches_reference() {
+        let (leaves, store) = test_tree(MAX_LEAVES);
+
+        for size in 0..=MAX_LEAVES {
+            let expected = reference_root(&leaves[..size as usize]);
+            assert_eq!(root_hash(&store, size), Some(expected), "size {}", size);
+        }
+    }
+
+    #[test]
+    fn inclusion_proofs_verify() {
+        let (leaves, store) = test_tree(MAX_LEAVES);
+
+        for size in 1..=MAX_LEAVES {
+            let root = reference_root(&leaves[..size as usize]);
+            for index in 0..size {
+                let proof = inclusion_proof(&store, index, size).unwrap();
+                let leaf = leaves[index as usize];
+
+                assert_eq!(
+                    proof,
+                    reference_path(index as usize, &leaves[..size as usize]),
+                    "index {}, size {}",
+                    index,
+                    size
+                );
+                assert!(verify_inclusion(index, size, leaf, &proof, root));
+                assert!(!verify_inclusion(index, size, leaf, &proof, empty_root()));
+                if let Some(last) = proof.len().checked_sub(1) {
+                    let mut tampered = proof.clone();
+                    tampered[last][0] ^= 1;
+                    assert!(!verify_inclusion(index, size, leaf, &tampered, root));
+                }
+            }
+            assert_eq!(inclusion_proof(&store, size, size), None);
+        }
+    }
+
+    #[test]
+    fn consistency_proofs_verify() {
+        let (leaves, store) = test_tree(MAX_LEAVES);
+
+        for second in 1..=MAX_LEAVES {
+            let second_root = reference_root(&leaves[..second as usize]);
+            for first in 1..=second {
+                let first_root = reference_root(&leaves[..first as usize]);
+                let proof = consistency_proof(&store, first, second).unwrap();
+
+                assert_eq!(
+                    proof,
+                    reference_consistency(first as usize, &leaves[..second as usize]),
+                    "first {}, second {}",
+                    first,
+                    second
+                );
+                assert!(verify_consistency(
+                    first,
+                    second,
+                    first_root,
+                    second_root,
+                    &proof
+                ));
+                if first < second {
+                    assert!(!verify_consistency(
+                        first, second, first_root, first_root, &proof
+                    ));
+                }
+            }
+            assert_eq!(consistency_proof(&store, 0, second), None);
+            assert_eq!(consistency_proof(&store, second + 1, second), None);
+        }
+    }
+
+    #[test]
+    fn append_nodes_needs_stored_siblings() {
+        let leaf = leaf_hash(b"leaf");
+        let store = HashMap::new();
+
+        assert_eq!(append_nodes(&store, 0, leaf), Some(vec![(0, 0, leaf)]));
+        assert_eq!(append_nodes(&store, 1, leaf), None);
+
+        // The leaf at index 3 completes subtrees at levels 1 and 2
+        let (_, mut store) = test_tree(3);
+        assert_eq!(append_nodes(&store, 3, leaf).unwrap().len(), 3);
+        store.remove(&(1, 0));
+        assert_eq!(append_nodes(&store, 3, leaf), None);
+    }
+}
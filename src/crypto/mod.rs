@@ -1,3 +1,8 @@
+pub mod key_format;
+pub mod merkle;
+pub mod signing;
+pub mod slip10;
+pub mod sshsig;
 pub mod utils;
 pub use ring;
 use std::convert::TryInto;
@@ -19,6 +24,14 @@ pub mod sign_ed25519 {
     const ELEM_LEN: usize = 32;
     const SIGNATURE_LEN: usize = ELEM_LEN + SCALAR_LEN;
     pub const ED25519_SIGNATURE_LEN: usize = SIGNATURE_LEN;
+    pub const ED25519_SEED_LEN: usize = 32;
+
+    // Layout of the PKCS8 v2 documents produced by ring: prefix, seed, public key tag, public key
+    const PKCS8_V2_PREFIX: [u8; 16] = [
+        0x30, 0x51, 0x02, 0x01, 0x01, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04,
+        0x20,
+    ];
+    const PKCS8_V2_PUBLIC_KEY_TAG: [u8; 3] = [0x81, 0x21, 0x00];
 
     /// Signature data
     /// We used sodiumoxide serialization before (treated it as slice with 64 bit length prefix).
@@ -108,6 +121,31 @@ pub mod sign_ed25519 {
         sm
     }
 
+    /// Builds a keypair, as a PKCS8 v2 document, from a raw 32 byte seed
+    pub fn keypair_from_seed(seed: &[u8]) -> Option<(PublicKey, SecretKey)> {
+        if seed.len() != ED25519_SEED_LEN {
+            return None;
+        }
+
+        let secret = SecretKeyBase::from_seed_unchecked(seed).ok()?;
+        let public = PublicKey(secret.public_key().as_ref().try_into().ok()?);
+
+        let mut pkcs8 = PKCS8_V2_PREFIX.to_vec();
+        pkcs8.extend_from_slice(seed);
+        pkcs8.extend_from_slice(&PKCS8_V2_PUBLIC_KEY_TAG);
+        pkcs8.extend_from_slice(public.as_ref());
+
+        SecretKeyBase::from_pkcs8(&pkcs8).ok()?;
+        Some((public, SecretKey(pkcs8)))
+    }
+
+    /// Loads a keypair from a PKCS8 v2 document, checking it with ring
+    pub fn keypair_from_pkcs8(pkcs8: &[u8]) -> Option<(PublicKey, SecretKey)> {
+        let secret = SecretKeyBase::from_pkcs8(pkcs8).ok()?;
+        let public = PublicKey(secret.public_key().as_ref().try_into().ok()?);
+        Some((public, SecretKey(pkcs8.to_vec())))
+    }
+
     pub fn gen_keypair() -> (PublicKey, SecretKey) {
         let rand = ring::rand::SystemRandom::new();
         let pkcs8 = SecretKeyBase::generate_pkcs8(&rand).unwrap();
@@ -118,6 +156,416 @@ pub mod sign_ed25519 {
     }
 }
 
+pub mod sign_ecdsa {
+    use ring::rand::SystemRandom;
+    use ring::signature::{
+        EcdsaKeyPair, EcdsaSigningAlgorithm, EcdsaVerificationAlgorithm, KeyPair,
+        UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING,
+        ECDSA_P256_SHA256_FIXED, ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_ASN1,
+        ECDSA_P384_SHA384_ASN1_SIGNING, ECDSA_P384_SHA384_FIXED, ECDSA_P384_SHA384_FIXED_SIGNING,
+    };
+    use serde::{Deserialize, Serialize};
+
+    /// Curves available for ECDSA signing. P-256 signs with SHA-256, P-384 with SHA-384
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum EcdsaCurve {
+        P256,
+        P384,
+    }
+
+    /// Encoding of an ECDSA signature: an ASN.1 DER `Ecdsa-Sig-Value`, or the
+    /// fixed-size `r || s` form used by JOSE and WebCrypto
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SignatureEncoding {
+        #[default]
+        Der,
+        Fixed,
+    }
+
+    impl EcdsaCurve {
+        /// Length of a fixed-size signature on this curve
+        pub fn fixed_signature_len(&self) -> usize {
+            match self {
+                EcdsaCurve::P256 => 64,
+                EcdsaCurve::P384 => 96,
+            }
+        }
+
+        /// Length of an uncompressed SEC1 public key on this curve
+        pub fn public_key_len(&self) -> usize {
+            match self {
+                EcdsaCurve::P256 => 65,
+                EcdsaCurve::P384 => 97,
+            }
+        }
+
+        fn signing_algorithm(&self, encoding: SignatureEncoding) -> &'static EcdsaSigningAlgorithm {
+            match (self, encoding) {
+                (EcdsaCurve::P256, SignatureEncoding::Der) => &ECDSA_P256_SHA256_ASN1_SIGNING,
+                (EcdsaCurve::P256, SignatureEncoding::Fixed) => &ECDSA_P256_SHA256_FIXED_SIGNING,
+                (EcdsaCurve::P384, SignatureEncoding::Der) => &ECDSA_P384_SHA384_ASN1_SIGNING,
+                (EcdsaCurve::P384, SignatureEncoding::Fixed) => &ECDSA_P384_SHA384_FIXED_SIGNING,
+            }
+        }
+
+        fn verification_algorithm(
+            &self,
+            encoding: SignatureEncoding,
+        ) -> &'static EcdsaVerificationAlgorithm {
+            match (self, encoding) {
+                (EcdsaCurve::P256, SignatureEncoding::Der) => &ECDSA_P256_SHA256_ASN1,
+                (EcdsaCurve::P256, SignatureEncoding::Fixed) => &ECDSA_P256_SHA256_FIXED,
+                (EcdsaCurve::P384, SignatureEncoding::Der) => &ECDSA_P384_SHA384_ASN1,
+                (EcdsaCurve::P384, SignatureEncoding::Fixed) => &ECDSA_P384_SHA384_FIXED,
+            }
+        }
+    }
+
+    /// Uncompressed SEC1 public key data
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PublicKey(Vec<u8>);
+
+    impl PublicKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            Some(Self(slice.to_vec()))
+        }
+    }
+
+    impl AsRef<[u8]> for PublicKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    /// PKCS8 encoded secret key pair
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SecretKey(Vec<u8>);
+
+    impl SecretKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            Some(Self(slice.to_vec()))
+        }
+    }
+
+    impl AsRef<[u8]> for SecretKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    /// Verifies a signature in either encoding. Fixed-size signatures have a length
+    /// that a DER encoded signature on the same curve can never have
+    pub fn verify_detached(curve: EcdsaCurve, sig: &[u8], msg: &[u8], pk: &PublicKey) -> bool {
+        let encoding = match sig.len() == curve.fixed_signature_len() {
+            true => SignatureEncoding::Fixed,
+            false => SignatureEncoding::Der,
+        };
+        let upk = UnparsedPublicKey::new(curve.verification_algorithm(encoding), pk);
+        upk.verify(msg, sig).is_ok()
+    }
+
+    pub fn sign_detached(
+        curve: EcdsaCurve,
+        encoding: SignatureEncoding,
+        msg: &[u8],
+        sk: &SecretKey,
+    ) -> Option<Vec<u8>> {
+        let rand = SystemRandom::new();
+        let secret =
+            EcdsaKeyPair::from_pkcs8(curve.signing_algorithm(encoding), sk.as_ref(), &rand).ok()?;
+        Some(secret.sign(&rand, msg).ok()?.as_ref().to_vec())
+    }
+
+    /// Loads a keypair from a PKCS8 document, checking it with ring
+    pub fn keypair_from_pkcs8(curve: EcdsaCurve, pkcs8: &[u8]) -> Option<(PublicKey, SecretKey)> {
+        let rand = SystemRandom::new();
+        let alg = curve.signing_algorithm(SignatureEncoding::Der);
+        let secret = EcdsaKeyPair::from_pkcs8(alg, pkcs8, &rand).ok()?;
+        let public = PublicKey(secret.public_key().as_ref().to_vec());
+        Some((public, SecretKey(pkcs8.to_vec())))
+    }
+
+    pub fn gen_keypair(curve: EcdsaCurve) -> (PublicKey, SecretKey) {
+        let rand = SystemRandom::new();
+        let alg = curve.signing_algorithm(SignatureEncoding::Der);
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(alg, &rand).unwrap();
+        keypair_from_pkcs8(curve, pkcs8.as_ref()).unwrap()
+    }
+}
+
+pub mod sign_rsa {
+    use ring::rand::SystemRandom;
+    use ring::signature::{
+        KeyPair, RsaEncoding, RsaKeyPair, RsaParameters, UnparsedPublicKey,
+        RSA_PKCS1_2048_8192_SHA256, RSA_PKCS1_SHA256, RSA_PSS_2048_8192_SHA256, RSA_PSS_SHA256,
+    };
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::pkcs8::EncodePrivateKey;
+    use rsa::traits::PublicKeyParts;
+    use serde::{Deserialize, Serialize};
+    use std::ops::RangeInclusive;
+
+    /// Modulus sizes, in bits, accepted for generated and imported keys
+    pub const RSA_KEY_BITS: RangeInclusive<usize> = 2048..=4096;
+
+    /// Modulus size used when none is requested
+    pub const RSA_DEFAULT_KEY_BITS: usize = 2048;
+
+    /// Padding scheme of an RSA signature. Both hash the message with SHA-256
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum RsaPadding {
+        Pss,
+        Pkcs1,
+    }
+
+    impl RsaPadding {
+        fn encoding(&self) -> &'static dyn RsaEncoding {
+            match self {
+                RsaPadding::Pss => &RSA_PSS_SHA256,
+                RsaPadding::Pkcs1 => &RSA_PKCS1_SHA256,
+            }
+        }
+
+        fn parameters(&self) -> &'static RsaParameters {
+            match self {
+                RsaPadding::Pss => &RSA_PSS_2048_8192_SHA256,
+                RsaPadding::Pkcs1 => &RSA_PKCS1_2048_8192_SHA256,
+            }
+        }
+    }
+
+    /// DER encoded PKCS1 RSAPublicKey
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PublicKey(Vec<u8>);
+
+    impl PublicKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            Some(Self(slice.to_vec()))
+        }
+    }
+
+    impl AsRef<[u8]> for PublicKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    /// PKCS8 encoded secret key pair
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SecretKey(Vec<u8>);
+
+    impl SecretKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            Some(Self(slice.to_vec()))
+        }
+    }
+
+    impl AsRef<[u8]> for SecretKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    /// Size of the modulus of a public key, in bits
+    pub fn modulus_bits(pk: &PublicKey) -> Option<usize> {
+        let public = rsa::RsaPublicKey::from_pkcs1_der(pk.as_ref()).ok()?;
+        Some(public.n().bits())
+    }
+
+    pub fn verify_detached(padding: RsaPadding, sig: &[u8], msg: &[u8], pk: &PublicKey) -> bool {
+        let upk = UnparsedPublicKey::new(padding.parameters(), pk);
+        upk.verify(msg, sig).is_ok()
+    }
+
+    pub fn sign_detached(padding: RsaPadding, msg: &[u8], sk: &SecretKey) -> Option<Vec<u8>> {
+        let secret = RsaKeyPair::from_pkcs8(sk.as_ref()).ok()?;
+        let mut signature = vec![0; secret.public().modulus_len()];
+        secret
+            .sign(
+                padding.encoding(),
+                &SystemRandom::new(),
+                msg,
+                &mut signature,
+            )
+            .ok()?;
+        Some(signature)
+    }
+
+    /// Loads a keypair from a PKCS8 document, checking it with ring and refusing
+    /// moduli outside `RSA_KEY_BITS`
+    pub fn keypair_from_pkcs8(pkcs8: &[u8]) -> Option<(PublicKey, SecretKey)> {
+        let secret = RsaKeyPair::from_pkcs8(pkcs8).ok()?;
+        if !RSA_KEY_BITS.contains(&(secret.public().modulus_len() * 8)) {
+            return None;
+        }
+
+        let public = PublicKey(secret.public_key().as_ref().to_vec());
+        Some((public, SecretKey(pkcs8.to_vec())))
+    }
+
+    /// Generates a keypair with a modulus of the given size. ring cannot generate
+    /// RSA keys, so this is done by the `rsa` crate
+    pub fn gen_keypair(bits: usize) -> Option<(PublicKey, SecretKey)> {
+        if !RSA_KEY_BITS.contains(&bits) {
+            return None;
+        }
+
+        let secret = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), bits).ok()?;
+        keypair_from_pkcs8(secret.to_pkcs8_der().ok()?.as_bytes())
+    }
+}
+
+pub mod sign_ml_dsa {
+    use super::generate_random;
+    use fips204::ml_dsa_65;
+    pub use fips204::ml_dsa_65::{
+        PK_LEN as ML_DSA_PUBLIC_KEY_LEN, SIG_LEN as ML_DSA_SIGNATURE_LEN,
+    };
+    use fips204::traits::{KeyGen, SerDes, Signer, Verifier};
+    use serde::{Deserialize, Serialize};
+
+    pub const ML_DSA_SEED_LEN: usize = 32;
+
+    /// ML-DSA-65 (FIPS 204) public key data
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PublicKey(Vec<u8>);
+
+    impl PublicKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            match slice.len() == ML_DSA_PUBLIC_KEY_LEN {
+                true => Some(Self(slice.to_vec())),
+                false => None,
+            }
+        }
+    }
+
+    impl AsRef<[u8]> for PublicKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    /// 32 byte seed that the ML-DSA-65 secret key is expanded from, the compact form
+    /// FIPS 204 allows storing instead of the full secret key
+    #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SecretKey(Vec<u8>);
+
+    impl SecretKey {
+        pub fn from_slice(slice: &[u8]) -> Option<Self> {
+            match slice.len() == ML_DSA_SEED_LEN {
+                true => Some(Self(slice.to_vec())),
+                false => None,
+            }
+        }
+    }
+
+    impl AsRef<[u8]> for SecretKey {
+        fn as_ref(&self) -> &[u8] {
+            self.0.as_ref()
+        }
+    }
+
+    pub fn verify_detached(sig: &[u8], msg: &[u8], ctx: &[u8], pk: &PublicKey) -> bool {
+        let (Ok(sig), Ok(pk)) = (sig.try_into(), pk.as_ref().try_into()) else {
+            return false;
+        };
+        match ml_dsa_65::PublicKey::try_from_bytes(pk) {
+            Ok(pk) => pk.verify(msg, sig, ctx),
+            Err(_) => false,
+        }
+    }
+
+    /// Signs a message with hedged (randomised) signing, under a context string of at
+    /// most 255 bytes
+    pub fn sign_detached(msg: &[u8], ctx: &[u8], sk: &SecretKey) -> Option<Vec<u8>> {
+        let (_, secret) = ml_dsa_65::KG::keygen_from_seed(&sk.0.as_slice().try_into().ok()?);
+        Some(secret.try_sign(msg, ctx).ok()?.to_vec())
+    }
+
+    /// Expands a keypair from a 32 byte seed
+    pub fn keypair_from_seed(seed: &[u8]) -> Option<(PublicKey, SecretKey)> {
+        let secret = SecretKey::from_slice(seed)?;
+        let (public, _) = ml_dsa_65::KG::keygen_from_seed(&seed.try_into().ok()?);
+        Some((PublicKey(public.into_bytes().to_vec()), secret))
+    }
+
+    pub fn gen_keypair() -> (PublicKey, SecretKey) {
+        keypair_from_seed(&generate_random::<ML_DSA_SEED_LEN>()).unwrap()
+    }
+}
+
+/// Ed25519 and ML-DSA-65 composite signatures. Keys and signatures are the Ed25519
+/// part followed by the ML-DSA part, and a signature is only valid if both parts are.
+/// Both parts are bound to the composite with a context, so that neither can be
+/// stripped off and passed as a plain signature: Ed25519 signs the context followed
+/// by the message, ML-DSA signs the message under the context string
+pub mod sign_hybrid {
+    use super::{sign_ed25519, sign_ml_dsa};
+    use sign_ed25519::{ED25519_PUBLIC_KEY_LEN, ED25519_SIGNATURE_LEN};
+    use sign_ml_dsa::{ML_DSA_PUBLIC_KEY_LEN, ML_DSA_SEED_LEN, ML_DSA_SIGNATURE_LEN};
+
+    pub const HYBRID_PUBLIC_KEY_LEN: usize = ED25519_PUBLIC_KEY_LEN + ML_DSA_PUBLIC_KEY_LEN;
+    pub const HYBRID_SIGNATURE_LEN: usize = ED25519_SIGNATURE_LEN + ML_DSA_SIGNATURE_LEN;
+
+    /// Context both signature parts are bound to
+    pub const HYBRID_CONTEXT: &[u8] = b"freemason-ed25519-ml-dsa-65";
+
+    pub fn verify_detached(sig: &[u8], msg: &[u8], pk: &[u8]) -> bool {
+        if sig.len() != HYBRID_SIGNATURE_LEN || pk.len() != HYBRID_PUBLIC_KEY_LEN {
+            return false;
+        }
+        let (ed_sig, ml_sig) = sig.split_at(ED25519_SIGNATURE_LEN);
+        let (ed_pk, ml_pk) = pk.split_at(ED25519_PUBLIC_KEY_LEN);
+
+        let ed_valid = match (
+            sign_ed25519::Signature::from_slice(ed_sig),
+            sign_ed25519::PublicKey::from_slice(ed_pk),
+        ) {
+            (Some(ed_sig), Some(ed_pk)) => {
+                sign_ed25519::verify_detached(&ed_sig, &bound_message(msg), &ed_pk)
+            }
+            _ => false,
+        };
+        let ml_valid = match sign_ml_dsa::PublicKey::from_slice(ml_pk) {
+            Some(ml_pk) => sign_ml_dsa::verify_detached(ml_sig, msg, HYBRID_CONTEXT, &ml_pk),
+            None => false,
+        };
+
+        ed_valid && ml_valid
+    }
+
+    /// Signs a message with both parts. The secret key is the Ed25519 PKCS8 document
+    /// followed by the ML-DSA seed
+    pub fn sign_detached(msg: &[u8], sk: &[u8]) -> Option<Vec<u8>> {
+        let split = sk.len().checked_sub(ML_DSA_SEED_LEN)?;
+        let (ed_sk, ml_sk) = sk.split_at(split);
+
+        let ed_sk = sign_ed25519::SecretKey::from_slice(ed_sk)?;
+        let mut signature = sign_ed25519::sign_detached(&bound_message(msg), &ed_sk)
+            .as_ref()
+            .to_vec();
+        let ml_sk = sign_ml_dsa::SecretKey::from_slice(ml_sk)?;
+        signature.extend(sign_ml_dsa::sign_detached(msg, HYBRID_CONTEXT, &ml_sk)?);
+        Some(signature)
+    }
+
+    /// Generates a keypair, returned as the raw public key and secret key
+    pub fn gen_keypair() -> (Vec<u8>, Vec<u8>) {
+        let (ed_pk, ed_sk) = sign_ed25519::gen_keypair();
+        let (ml_pk, ml_sk) = sign_ml_dsa::gen_keypair();
+        (
+            [ed_pk.as_ref(), ml_pk.as_ref()].concat(),
+            [ed_sk.as_ref(), ml_sk.as_ref()].concat(),
+        )
+    }
+
+    /// Message the Ed25519 part signs
+    fn bound_message(msg: &[u8]) -> Vec<u8> {
+        [HYBRID_CONTEXT, msg].concat()
+    }
+}
+
 pub mod secretbox_chacha20_poly1305 {
     use super::utils::{generate_key, generate_nonce};
     // Use key and nonce separately like rust-tls does
@@ -149,6 +597,12 @@ pub mod secretbox_chacha20_poly1305 {
         }
     }
 
+    impl Default for Key {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl AsRef<[u8]> for Key {
         fn as_ref(&self) -> &[u8] {
             self.0.as_ref()
@@ -173,6 +627,12 @@ pub mod secretbox_chacha20_poly1305 {
         }
     }
 
+    impl Default for Nonce {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl AsRef<[u8]> for Nonce {
         fn as_ref(&self) -> &[u8] {
             self.0.as_ref()
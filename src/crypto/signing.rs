@@ -0,0 +1,185 @@
+use crate::crypto::sign_ecdsa::{self, EcdsaCurve, SignatureEncoding};
+use crate::crypto::sign_ed25519;
+use crate::crypto::sign_hybrid;
+use crate::crypto::sign_ml_dsa;
+use crate::crypto::sign_rsa::{self, RsaPadding};
+use serde::{Deserialize, Serialize};
+
+/// Raw public key and PKCS8 secret key of a signing keypair
+pub type RawKeypair = (Vec<u8>, Vec<u8>);
+
+/// Signature algorithms a signing keypair can use
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SigningAlgorithm {
+    #[default]
+    Ed25519,
+    EcdsaP256,
+    EcdsaP384,
+    RsaPssSha256,
+    RsaPkcs1Sha256,
+    #[serde(rename = "ml-dsa-65")]
+    MlDsa65,
+    #[serde(rename = "ed25519-ml-dsa-65")]
+    Ed25519MlDsa65,
+}
+
+impl SigningAlgorithm {
+    /// ECDSA curve of the algorithm, if it is an ECDSA algorithm
+    pub fn ecdsa_curve(&self) -> Option<EcdsaCurve> {
+        match self {
+            SigningAlgorithm::EcdsaP256 => Some(EcdsaCurve::P256),
+            SigningAlgorithm::EcdsaP384 => Some(EcdsaCurve::P384),
+            _ => None,
+        }
+    }
+
+    /// JOSE name of the algorithm (RFC 7518, RFC 8037, and the JOSE ML-DSA draft), as
+    /// used in JWS headers and JWKs. The hybrid algorithm has none
+    pub fn jws_alg(&self) -> Option<&'static str> {
+        match self {
+            SigningAlgorithm::Ed25519 => Some("EdDSA"),
+            SigningAlgorithm::EcdsaP256 => Some("ES256"),
+            SigningAlgorithm::EcdsaP384 => Some("ES384"),
+            SigningAlgorithm::RsaPssSha256 => Some("PS256"),
+            SigningAlgorithm::RsaPkcs1Sha256 => Some("RS256"),
+            SigningAlgorithm::MlDsa65 => Some("ML-DSA-65"),
+            SigningAlgorithm::Ed25519MlDsa65 => None,
+        }
+    }
+
+    /// RSA padding scheme of the algorithm, if it is an RSA algorithm
+    pub fn rsa_padding(&self) -> Option<RsaPadding> {
+        match self {
+            SigningAlgorithm::RsaPssSha256 => Some(RsaPadding::Pss),
+            SigningAlgorithm::RsaPkcs1Sha256 => Some(RsaPadding::Pkcs1),
+            _ => None,
+        }
+    }
+}
+
+/// Generates a new keypair, returned as the raw public key and PKCS8 secret key, or
+/// the seed for ML-DSA. Returns None if the RSA key size is out of range
+///
+/// ### Arguments
+///
+/// * `algorithm` - Signature algorithm of the keypair
+/// * `rsa_bits` - Modulus size of RSA keys, ignored for other algorithms
+pub fn gen_keypair(algorithm: SigningAlgorithm, rsa_bits: usize) -> Option<RawKeypair> {
+    if let Some(curve) = algorithm.ecdsa_curve() {
+        let (public, secret) = sign_ecdsa::gen_keypair(curve);
+        return Some((public.as_ref().to_vec(), secret.as_ref().to_vec()));
+    }
+
+    if algorithm.rsa_padding().is_some() {
+        let (public, secret) = sign_rsa::gen_keypair(rsa_bits)?;
+        return Some((public.as_ref().to_vec(), secret.as_ref().to_vec()));
+    }
+
+    match algorithm {
+        SigningAlgorithm::MlDsa65 => {
+            let (public, secret) = sign_ml_dsa::gen_keypair();
+            Some((public.as_ref().to_vec(), secret.as_ref().to_vec()))
+        }
+        SigningAlgorithm::Ed25519MlDsa65 => Some(sign_hybrid::gen_keypair()),
+        _ => {
+            let (public, secret) = sign_ed25519::gen_keypair();
+            Some((public.as_ref().to_vec(), secret.as_ref().to_vec()))
+        }
+    }
+}
+
+/// Signs a message. The encoding only applies to ECDSA signatures
+///
+/// ### Arguments
+///
+/// * `algorithm` - Signature algorithm of the keypair
+/// * `encoding` - Encoding of ECDSA signatures
+/// * `message` - Message to sign
+/// * `secret_key` - PKCS8 secret key, or the seed for ML-DSA
+pub fn sign(
+    algorithm: SigningAlgorithm,
+    encoding: SignatureEncoding,
+    message: &[u8],
+    secret_key: &[u8],
+) -> Option<Vec<u8>> {
+    if let Some(curve) = algorithm.ecdsa_curve() {
+        let secret_key = sign_ecdsa::SecretKey::from_slice(secret_key)?;
+        return sign_ecdsa::sign_detached(curve, encoding, message, &secret_key);
+    }
+
+    if let Some(padding) = algorithm.rsa_padding() {
+        let secret_key = sign_rsa::SecretKey::from_slice(secret_key)?;
+        return sign_rsa::sign_detached(padding, message, &secret_key);
+    }
+
+    match algorithm {
+        SigningAlgorithm::MlDsa65 => {
+            let secret_key = sign_ml_dsa::SecretKey::from_slice(secret_key)?;
+            sign_ml_dsa::sign_detached(message, &[], &secret_key)
+        }
+        SigningAlgorithm::Ed25519MlDsa65 => sign_hybrid::sign_detached(message, secret_key),
+        _ => {
+            let secret_key = sign_ed25519::SecretKey::from_slice(secret_key)?;
+            Some(
+                sign_ed25519::sign_detached(message, &secret_key)
+                    .as_ref()
+                    .to_vec(),
+            )
+        }
+    }
+}
+
+/// Verifies a signature over a message
+///
+/// ### Arguments
+///
+/// * `algorithm` - Signature algorithm of the keypair
+/// * `signature` - Signature to verify
+/// * `message` - Signed message
+/// * `public_key` - Raw public key
+pub fn verify(
+    algorithm: SigningAlgorithm,
+    signature: &[u8],
+    message: &[u8],
+    public_key: &[u8],
+) -> bool {
+    if let Some(curve) = algorithm.ecdsa_curve() {
+        return match sign_ecdsa::PublicKey::from_slice(public_key) {
+            Some(public_key) => sign_ecdsa::verify_detached(curve, signature, message, &public_key),
+            None => false,
+        };
+    }
+
+    if let Some(padding) = algorithm.rsa_padding() {
+        return match sign_rsa::PublicKey::from_slice(public_key) {
+            Some(public_key) => sign_rsa::verify_detached(padding, signature, message, &public_key),
+            None => false,
+        };
+    }
+
+    match algorithm {
+        SigningAlgorithm::MlDsa65 => {
+            return match sign_ml_dsa::PublicKey::from_slice(public_key) {
+                Some(public_key) => {
+                    sign_ml_dsa::verify_detached(signature, message, &[], &public_key)
+                }
+                None => false,
+            }
+        }
+        SigningAlgorithm::Ed25519MlDsa65 => {
+            return sign_hybrid::verify_detached(signature, message, public_key)
+        }
+        _ => {}
+    }
+
+    match (
+        sign_ed25519::Signature::from_slice(signature),
+        sign_ed25519::PublicKey::from_slice(public_key),
+    ) {
+        (Some(signature), Some(public_key)) => {
+            sign_ed25519::verify_detached(&signature, message, &public_key)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,288 @@
+use crate::crypto::sha3_256;
+use ring::hmac;
+
+/// Offset of hardened child indices. Ed25519 only supports hardened derivation
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// Shortest and longest master seeds allowed by BIP-32
+pub const MIN_SEED_LEN: usize = 16;
+pub const MAX_SEED_LEN: usize = 64;
+
+/// Number of indices in the derivation path of an ID, before the version index
+const ID_PATH_DEPTH: usize = 4;
+
+/// HMAC key of the master key derivation for the ed25519 curve
+const ED25519_CURVE_KEY: &[u8] = b"ed25519 seed";
+
+/// Private key and chain code of a node in the derivation tree
+#[derive(Clone)]
+pub struct ExtendedKey {
+    pub key: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    /// Master node of a seed
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed` - Master seed
+    pub fn master(seed: &[u8]) -> ExtendedKey {
+        hmac_split(ED25519_CURVE_KEY, &[seed])
+    }
+
+    /// Hardened child of this node
+    ///
+    /// ### Arguments
+    ///
+    /// * `index` - Child index, with or without the hardened offset
+    pub fn child(&self, index: u32) -> ExtendedKey {
+        let index = index | HARDENED_OFFSET;
+        hmac_split(&self.chain_code, &[&[0], &self.key, &index.to_be_bytes()])
+    }
+}
+
+/// Derives the Ed25519 seed of the node at a path
+///
+/// ### Arguments
+///
+/// * `seed` - Master seed
+/// * `path` - Hardened child indices from the master node down
+pub fn derive_seed(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    path.iter()
+        .fold(ExtendedKey::master(seed), |node, index| node.child(*index))
+        .key
+}
+
+/// Parses a derivation path such as `m/44'/0'/1'`. Every index must be hardened,
+/// marked with `'` or `h`
+///
+/// ### Arguments
+///
+/// * `path` - Derivation path
+pub fn parse_path(path: &str) -> Option<Vec<u32>> {
+    let mut segments = path.trim().split('/');
+    if segments.next()? != "m" {
+        return None;
+    }
+
+    segments
+        .map(|segment| {
+            let index = segment
+                .strip_suffix('\'')
+                .or_else(|| segment.strip_suffix('h'))?;
+            let index: u32 = index.parse().ok()?;
+            (index < HARDENED_OFFSET).then_some(index | HARDENED_OFFSET)
+        })
+        .collect()
+}
+
+/// Formats hardened child indices as a derivation path
+///
+/// ### Arguments
+///
+/// * `path` - Hardened child indices
+pub fn format_path(path: &[u32]) -> String {
+    path.iter().fold("m".to_string(), |formatted, index| {
+        format!("{}/{}'", formatted, index & !HARDENED_OFFSET)
+    })
+}
+
+/// Derivation path of the first version of an ID's keypair, taken from the SHA3-256
+/// digest of the ID and ending in the version index 0
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+pub fn id_path(id: &str) -> Vec<u32> {
+    let digest = sha3_256::digest(id.as_bytes());
+    let mut path: Vec<u32> = digest
+        .chunks_exact(4)
+        .take(ID_PATH_DEPTH)
+        .map(|chunk| u32::from_be_bytes(chunk.try_into().unwrap()) | HARDENED_OFFSET)
+        .collect();
+    path.push(HARDENED_OFFSET);
+    path
+}
+
+/// Path of the next version of a derived keypair, which increments the last index.
+/// Returns None if the last index cannot be incremented
+///
+/// ### Arguments
+///
+/// * `path` - Hardened child indices of the current version
+pub fn next_path(path: &[u32]) -> Option<Vec<u32>> {
+    let (last, parent) = path.split_last()?;
+    let next = last.checked_add(1)?;
+
+    let mut path = parent.to_vec();
+    path.push(next);
+    Some(path)
+}
+
+/// HMAC-SHA512 of the data, split into the left half as key and right as chain code
+fn hmac_split(key: &[u8], data: &[&[u8]]) -> ExtendedKey {
+    let key = hmac::Key::new(hmac::HMAC_SHA512, key);
+    let mut context = hmac::Context::with_key(&key);
+    data.iter().for_each(|data| context.update(data));
+    let tag = context.sign();
+
+    let (key, chain_code) = tag.as_ref().split_at(32);
+    ExtendedKey {
+        key: key.try_into().unwrap(),
+        chain_code: chain_code.try_into().unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::sign_ed25519;
+
+    /// Path, chain code, private key and public key of a SLIP-0010 ed25519 test
+    /// vector node. Public keys carry the SLIP-0010 `00` prefix
+    type VectorNode = (&'static str, &'static str, &'static str, &'static str);
+
+    const VECTOR_1_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+    const VECTOR_1: [VectorNode; 6] = [
+        (
+            "m",
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            "00a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+        ),
+        (
+            "m/0'",
+            "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            "008c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+        ),
+        (
+            "m/0'/1'",
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            "001932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+        ),
+        (
+            "m/0'/1'/2'",
+            "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+            "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+            "00ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+        ),
+        (
+            "m/0'/1'/2'/2'",
+            "8f6d87f93d750e0efccda017d662a1b31a266e4a6f5993b15f5c1f07f74dd5cc",
+            "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+            "008abae2d66361c879b900d204ad2cc4984fa2aa344dd7ddc46007329ac76c429c",
+        ),
+        (
+            "m/0'/1'/2'/2'/1000000000'",
+            "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            "003c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a",
+        ),
+    ];
+
+    const VECTOR_2_SEED: &str = "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542";
+    const VECTOR_2: [VectorNode; 6] = [
+        (
+            "m",
+            "ef70a74db9c3a5af931b5fe73ed8e1a53464133654fd55e7a66f8570b8e33c3b",
+            "171cb88b1b3c1db25add599712e36245d75bc65a1a5c9e18d76f9f2b1eab4012",
+            "008fe9693f8fa62a4305a140b9764c5ee01e455963744fe18204b4fb948249308a",
+        ),
+        (
+            "m/0'",
+            "0b78a3226f915c082bf118f83618a618ab6dec793752624cbeb622acb562862d",
+            "1559eb2bbec5790b0c65d8693e4d0875b1747f4970ae8b650486ed7470845635",
+            "0086fab68dcb57aa196c77c5f264f215a112c22a912c10d123b0d03c3c28ef1037",
+        ),
+        (
+            "m/0'/2147483647'",
+            "138f0b2551bcafeca6ff2aa88ba8ed0ed8de070841f0c4ef0165df8181eaad7f",
+            "ea4f5bfe8694d8bb74b7b59404632fd5968b774ed545e810de9c32a4fb4192f4",
+            "005ba3b9ac6e90e83effcd25ac4e58a1365a9e35a3d3ae5eb07b9e4d90bcf7506d",
+        ),
+        (
+            "m/0'/2147483647'/1'",
+            "73bd9fff1cfbde33a1b846c27085f711c0fe2d66fd32e139d3ebc28e5a4a6b90",
+            "3757c7577170179c7868353ada796c839135b3d30554bbb74a4b1e4a5a58505c",
+            "002e66aa57069c86cc18249aecf5cb5a9cebbfd6fadeab056254763874a9352b45",
+        ),
+        (
+            "m/0'/2147483647'/1'/2147483646'",
+            "0902fe8a29f9140480a00ef244bd183e8a13288e4412d8389d140aac1794825a",
+            "5837736c89570de861ebc173b1086da4f505d4adb387c6a1b1342d5e4ac9ec72",
+            "00e33c0f7d81d843c572275f287498e8d408654fdf0d1e065b84e2e6f157aab09b",
+        ),
+        (
+            "m/0'/2147483647'/1'/2147483646'/2'",
+            "5d70af781f3a37b829f0d060924d5e960bdc02e85423494afc0b1a41bbe196d4",
+            "551d333177df541ad876a60ea71f00447931c0a9da16f227c11ea080d7391b8d",
+            "0047150c75db263559a70d5778bf36abbab30fb061ad69f69ece61a72b0cfa4fc0",
+        ),
+    ];
+
+    /// Checks every node of a test vector, walking down from the master node
+    fn check_vector(seed: &str, nodes: &[VectorNode]) {
+        let seed = hex::decode(seed).unwrap();
+
+        for (path, chain_code, private_key, public_key) in nodes {
+            let indices = parse_path(path).unwrap();
+            let node = indices
+                .iter()
+                .fold(ExtendedKey::master(&seed), |node, index| node.child(*index));
+            let (derived_public_key, _) = sign_ed25519::keypair_from_seed(&node.key).unwrap();
+
+            assert_eq!(hex::encode(node.chain_code), *chain_code, "{}", path);
+            assert_eq!(hex::encode(node.key), *private_key, "{}", path);
+            assert_eq!(derive_seed(&seed, &indices), node.key, "{}", path);
+            assert_eq!(
+                format!("00{}", hex::encode(derived_public_key)),
+                *public_key,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn slip10_test_vector_1() {
+        check_vector(VECTOR_1_SEED, &VECTOR_1);
+    }
+
+    #[test]
+    fn slip10_test_vector_2() {
+        check_vector(VECTOR_2_SEED, &VECTOR_2);
+    }
+
+    #[test]
+    fn path_round_trip() {
+        let path = vec![
+            HARDENED_OFFSET,
+            1 | HARDENED_OFFSET,
+            (HARDENED_OFFSET - 1) | HARDENED_OFFSET,
+        ];
+
+        assert_eq!(format_path(&path), "m/0'/1'/2147483647'");
+        assert_eq!(parse_path(&format_path(&path)), Some(path.clone()));
+        assert_eq!(parse_path("m/0h/1'/2147483647h"), Some(path));
+        assert_eq!(parse_path("m"), Some(Vec::new()));
+        assert_eq!(format_path(&[]), "m");
+
+        let id_path = id_path("key");
+        assert_eq!(parse_path(&format_path(&id_path)), Some(id_path));
+    }
+
+    #[test]
+    fn parse_path_rejects_invalid_paths() {
+        assert_eq!(parse_path("m/0'/1"), None);
+        assert_eq!(parse_path("m/2147483648'"), None);
+        assert_eq!(parse_path("m/4294967296'"), None);
+        assert_eq!(parse_path("0'/1'"), None);
+        assert_eq!(parse_path("/0'/1'"), None);
+        assert_eq!(parse_path("m/"), None);
+        assert_eq!(parse_path("m/-1'"), None);
+        assert_eq!(parse_path(""), None);
+    }
+}
@@ -0,0 +1,654 @@
+use crate::crypto::key_format::{
+    decode_pem, encode_pem, public_key_to_ssh_wire, put_ssh_string, SSH_ED25519,
+};
+use crate::crypto::sign_ed25519::{self, PublicKey, Signature, ED25519_PUBLIC_KEY_LEN};
+use crate::crypto::signing::SigningAlgorithm;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use ring::digest;
+
+/// PEM label of an armored SSH signature
+const SSHSIG_PEM_LABEL: &str = "SSH SIGNATURE";
+
+/// Preamble of both the signature blob and the signed data
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+/// Only version of the signature blob format
+const SSHSIG_VERSION: u32 = 1;
+
+/// Message hash algorithms allowed in an SSH signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshsigHash {
+    Sha256,
+    Sha512,
+}
+
+impl SshsigHash {
+    /// Name of the hash algorithm in the signature blob
+    pub fn name(&self) -> &'static str {
+        match self {
+            SshsigHash::Sha256 => "sha256",
+            SshsigHash::Sha512 => "sha512",
+        }
+    }
+
+    /// Looks up a hash algorithm by its name in the signature blob
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the hash algorithm
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"sha256" => Some(SshsigHash::Sha256),
+            b"sha512" => Some(SshsigHash::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Hashes a message
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Message to hash
+    pub fn digest(&self, message: &[u8]) -> Vec<u8> {
+        let algorithm = match self {
+            SshsigHash::Sha256 => &digest::SHA256,
+            SshsigHash::Sha512 => &digest::SHA512,
+        };
+        digest::digest(algorithm, message).as_ref().to_vec()
+    }
+}
+
+/// Decoded SSH signature over a message, made with an Ed25519 key
+#[derive(Debug, Clone)]
+pub struct Sshsig {
+    pub public_key: Vec<u8>,
+    pub namespace: String,
+    pub hash: SshsigHash,
+    pub signature: Vec<u8>,
+}
+
+impl Sshsig {
+    /// Decodes an armored SSH signature as produced by `ssh-keygen -Y sign`. Returns
+    /// None if it is malformed or not made with an Ed25519 key
+    ///
+    /// ### Arguments
+    ///
+    /// * `armored` - Armored signature
+    pub fn from_armored(armored: &str) -> Option<Self> {
+        let blob = decode_pem(armored, SSHSIG_PEM_LABEL)?;
+        let mut reader = SshReader(blob.strip_prefix(SSHSIG_MAGIC)?);
+        if reader.u32()? != SSHSIG_VERSION {
+            return None;
+        }
+        let public_key = ed25519_from_ssh_wire(reader.string()?)?;
+        let namespace = String::from_utf8(reader.string()?.to_vec()).ok()?;
+        let _reserved = reader.string()?;
+        let hash = SshsigHash::from_name(reader.string()?)?;
+
+        let mut sig_reader = SshReader(reader.string()?);
+        if !reader.0.is_empty() || sig_reader.string()? != SSH_ED25519.as_bytes() {
+            return None;
+        }
+        let signature = sig_reader.string()?.to_vec();
+        match sig_reader.0.is_empty() {
+            true => Some(Sshsig {
+                public_key,
+                namespace,
+                hash,
+                signature,
+            }),
+            false => None,
+        }
+    }
+
+    /// Encodes the signature in the armored form read by `ssh-keygen -Y verify`.
+    /// Returns None if the public key is not a raw Ed25519 key
+    pub fn to_armored(&self) -> Option<String> {
+        let mut signature = Vec::new();
+        put_ssh_string(&mut signature, SSH_ED25519.as_bytes());
+        put_ssh_string(&mut signature, &self.signature);
+
+        let mut blob = SSHSIG_MAGIC.to_vec();
+        blob.extend_from_slice(&SSHSIG_VERSION.to_be_bytes());
+        put_ssh_string(&mut blob, &self.public_key_wire()?);
+        put_ssh_string(&mut blob, self.namespace.as_bytes());
+        put_ssh_string(&mut blob, &[]);
+        put_ssh_string(&mut blob, self.hash.name().as_bytes());
+        put_ssh_string(&mut blob, &signature);
+
+        Some(encode_pem(&blob, SSHSIG_PEM_LABEL))
+    }
+
+    /// Checks the signature over a message
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Signed message
+    pub fn verify(&self, message: &[u8]) -> bool {
+        let (Some(signature), Some(public_key)) = (
+            Signature::from_slice(&self.signature),
+            PublicKey::from_slice(&self.public_key),
+        ) else {
+            return false;
+        };
+        let signed_data = signed_data(&self.namespace, self.hash, message);
+        sign_ed25519::verify_detached(&signature, &signed_data, &public_key)
+    }
+
+    /// SHA256 fingerprint of the public key, as shown by `ssh-keygen -l`
+    pub fn fingerprint(&self) -> Option<String> {
+        let digest = digest::digest(&digest::SHA256, &self.public_key_wire()?);
+        Some(format!("SHA256:{}", STANDARD_NO_PAD.encode(digest)))
+    }
+
+    /// Public key in the SSH wire format
+    fn public_key_wire(&self) -> Option<Vec<u8>> {
+        match self.public_key.len() == ED25519_PUBLIC_KEY_LEN {
+            true => public_key_to_ssh_wire(SigningAlgorithm::Ed25519, &self.public_key),
+            false => None,
+        }
+    }
+}
+
+/// Data an SSH signature is made over: the magic preamble, namespace, reserved
+/// field, hash algorithm and hash of the message
+///
+/// ### Arguments
+///
+/// * `namespace` - Namespace of the signature, e.g. "git" or "file"
+/// * `hash` - Hash algorithm of the message
+/// * `message` - Message to sign
+pub fn signed_data(namespace: &str, hash: SshsigHash, message: &[u8]) -> Vec<u8> {
+    let mut data = SSHSIG_MAGIC.to_vec();
+    put_ssh_string(&mut data, namespace.as_bytes());
+    put_ssh_string(&mut data, &[]);
+    put_ssh_string(&mut data, hash.name().as_bytes());
+    put_ssh_string(&mut data, &hash.digest(message));
+    data
+}
+
+/// Entry of an `allowed_signers` list that holds an Ed25519 key
+#[derive(Debug, Clone)]
+pub struct AllowedSigner {
+    pub principals: String,
+    pub namespaces: Option<Vec<String>>,
+    pub valid_after: Option<u64>,
+    pub valid_before: Option<u64>,
+    pub public_key: Vec<u8>,
+}
+
+impl AllowedSigner {
+    /// Whether this entry allows a key to sign for a namespace at a given time
+    ///
+    /// ### Arguments
+    ///
+    /// * `public_key` - Raw Ed25519 public key of the signature
+    /// * `namespace` - Namespace of the signature
+    /// * `now` - Current UNIX timestamp
+    pub fn allows(&self, public_key: &[u8], namespace: &str, now: u64) -> bool {
+        self.public_key == public_key
+            && self
+                .namespaces
+                .as_ref()
+                .is_none_or(|namespaces| match_pattern_list(namespace, namespaces))
+            && self.valid_after.is_none_or(|after| now >= after)
+            && self.valid_before.is_none_or(|before| now < before)
+    }
+
+    /// Whether a principal matches the principal patterns of this entry
+    ///
+    /// ### Arguments
+    ///
+    /// * `principal` - Principal to match, e.g. an email address
+    pub fn matches_principal(&self, principal: &str) -> bool {
+        let patterns: Vec<String> = self.principals.split(',').map(str::to_string).collect();
+        match_pattern_list(principal, &patterns)
+    }
+}
+
+/// Parses an `allowed_signers` list in the format of ssh-keygen(1). Entries of other
+/// key types are skipped, as are `cert-authority` entries since only plain keys can
+/// be checked. Times in `valid-after` and `valid-before` are taken as UTC. Returns
+/// the 1-based number of the first malformed line on failure
+///
+/// ### Arguments
+///
+/// * `list` - Contents of the allowed_signers file
+pub fn parse_allowed_signers(list: &str) -> Result<Vec<AllowedSigner>, usize> {
+    let mut signers = Vec::new();
+    for (number, line) in list.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_allowed_signer(line) {
+            Some(Some(signer)) => signers.push(signer),
+            Some(None) => {}
+            None => return Err(number + 1),
+        }
+    }
+    Ok(signers)
+}
+
+/// Parses one `allowed_signers` line. Returns Some(None) for valid entries that
+/// cannot match an Ed25519 signature
+///
+/// ### Arguments
+///
+/// * `line` - Line to parse
+fn parse_allowed_signer(line: &str) -> Option<Option<AllowedSigner>> {
+    let fields = split_fields(line)?;
+    let mut fields = fields.iter().map(String::as_str);
+    let principals = fields.next()?.to_string();
+
+    let mut key_type = fields.next()?;
+    let mut namespaces = None;
+    let mut valid_after = None;
+    let mut valid_before = None;
+    let mut cert_authority = false;
+    if !is_key_type(key_type) {
+        for option in split_outside_quotes(key_type, ',') {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(unquote(value)?)),
+                None => (option, None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("cert-authority", None) => cert_authority = true,
+                ("namespaces", Some(value)) => {
+                    namespaces = Some(value.split(',').map(str::to_string).collect())
+                }
+                ("valid-after", Some(value)) => valid_after = Some(parse_ssh_time(&value)?),
+                ("valid-before", Some(value)) => valid_before = Some(parse_ssh_time(&value)?),
+                _ => return None,
+            }
+        }
+        key_type = fields.next()?;
+    }
+
+    let blob = STANDARD.decode(fields.next()?).ok()?;
+    if cert_authority || key_type != SSH_ED25519 {
+        return Some(None);
+    }
+    Some(Some(AllowedSigner {
+        principals,
+        namespaces,
+        valid_after,
+        valid_before,
+        public_key: ed25519_from_ssh_wire(&blob)?,
+    }))
+}
+
+/// Whether a field of an `allowed_signers` line names an SSH key type rather than
+/// holding options
+///
+/// ### Arguments
+///
+/// * `field` - Field to check
+fn is_key_type(field: &str) -> bool {
+    ["ssh-", "ecdsa-sha2-", "sk-", "rsa-sha2-"]
+        .iter()
+        .any(|prefix| field.starts_with(prefix))
+}
+
+/// Splits a line into whitespace separated fields, keeping double quoted runs
+/// together. Returns None on an unterminated quote
+///
+/// ### Arguments
+///
+/// * `line` - Line to split
+fn split_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                field.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !field.is_empty() {
+                    fields.push(std::mem::take(&mut field));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+
+    // The principals field may itself be quoted
+    if let Some(principals) = fields.first_mut() {
+        *principals = principals.replace('"', "");
+    }
+    Some(fields)
+}
+
+/// Splits a string on a separator that is not inside double quotes
+///
+/// ### Arguments
+///
+/// * `s` - String to split
+/// * `separator` - Separator character
+fn split_outside_quotes(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Strips the double quotes around an option value, if it has them
+///
+/// ### Arguments
+///
+/// * `value` - Option value
+fn unquote(value: &str) -> Option<String> {
+    match value.strip_prefix('"') {
+        Some(rest) => Some(rest.strip_suffix('"')?.to_string()),
+        None => Some(value.to_string()),
+    }
+}
+
+/// Parses a `YYYYMMDD[HHMM[SS]]` time, optionally suffixed with `Z`, as a UNIX
+/// timestamp in UTC
+///
+/// ### Arguments
+///
+/// * `time` - Time to parse
+fn parse_ssh_time(time: &str) -> Option<u64> {
+    let time = time.strip_suffix(['Z', 'z']).unwrap_or(time);
+    if ![8, 12, 14].contains(&time.len()) || !time.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| time.get(range).map(|f| f.parse::<u64>().ok());
+    let year = field(0..4)??;
+    let month = field(4..6)??;
+    let day = field(6..8)??;
+    let hour = field(8..10).unwrap_or(Some(0))?;
+    let minute = field(10..12).unwrap_or(Some(0))?;
+    let second = field(12..14).unwrap_or(Some(0))?;
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // Days since the epoch of a proleptic Gregorian date, counting years from March
+    let (year, month) = match month <= 2 {
+        true => (year - 1, month + 9),
+        false => (year, month - 3),
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+/// Whether a string matches a comma separated pattern list in the style of
+/// ssh_config(5): `*` and `?` are wildcards, and a match on a pattern negated
+/// with `!` rules the string out
+///
+/// ### Arguments
+///
+/// * `s` - String to match
+/// * `patterns` - Patterns to match against
+fn match_pattern_list(s: &str, patterns: &[String]) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if match_pattern(s.as_bytes(), negated.as_bytes()) => return false,
+            Some(_) => {}
+            None => matched |= match_pattern(s.as_bytes(), pattern.as_bytes()),
+        }
+    }
+    matched
+}
+
+/// Whether a string matches a wildcard pattern with `*` and `?`
+///
+/// ### Arguments
+///
+/// * `s` - String to match
+/// * `pattern` - Wildcard pattern
+fn match_pattern(s: &[u8], pattern: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|skip| match_pattern(&s[skip..], rest)),
+        Some((b'?', rest)) => !s.is_empty() && match_pattern(&s[1..], rest),
+        Some((c, rest)) => s.first() == Some(c) && match_pattern(&s[1..], rest),
+    }
+}
+
+/// Extracts the raw key from an `ssh-ed25519` public key in the SSH wire format
+///
+/// ### Arguments
+///
+/// * `blob` - Public key in the SSH wire format
+fn ed25519_from_ssh_wire(blob: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = SshReader(blob);
+    if reader.string()? != SSH_ED25519.as_bytes() {
+        return None;
+    }
+    let public_key = reader.string()?;
+    match reader.0.is_empty() && public_key.len() == ED25519_PUBLIC_KEY_LEN {
+        true => Some(public_key.to_vec()),
+        false => None,
+    }
+}
+
+/// Reads values of the SSH wire format (RFC 4251) from the front of a buffer
+struct SshReader<'a>(&'a [u8]);
+
+impl<'a> SshReader<'a> {
+    /// Reads a big-endian u32
+    fn u32(&mut self) -> Option<u32> {
+        let (value, rest) = self.0.split_first_chunk::<4>()?;
+        self.0 = rest;
+        Some(u32::from_be_bytes(*value))
+    }
+
+    /// Reads a length-prefixed string
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            return None;
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Message signed by `ssh-keygen -Y sign -f key -n file` to make `FIXTURE_SIG`
+    const FIXTURE_MESSAGE: &[u8] = b"Freemason sshsig fixture\n";
+
+    const FIXTURE_SIG: &str = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgwy7go8tOCAjlhcMJEMBGJrjLKX
+NKqVkmKJkbmPSJz6EAAAAEZmlsZQAAAAAAAAAGc2hhNTEyAAAAUwAAAAtzc2gtZWQyNTUx
+OQAAAECGv8JTcH/lFcPGV/nyRm/u2ccw7KSz6fVDII/boBZqOzA/NaZpNoJwuW8eStMCyx
+/ojEDB9pnVd11mOfnudWgF
+-----END SSH SIGNATURE-----
+";
+
+    /// Public key of the fixture signature, as in the `.pub` file from ssh-keygen
+    const FIXTURE_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMMu4KPLTggI5YXDCRDARia4yylzSqlZJiiZG5j0ic+h";
+
+    /// Raw Ed25519 key of `FIXTURE_KEY`
+    fn fixture_public_key() -> Vec<u8> {
+        let blob = STANDARD.decode(FIXTURE_KEY.split(' ').nth(1).unwrap());
+        ed25519_from_ssh_wire(&blob.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ssh_keygen_signature_verifies() {
+        let sshsig = Sshsig::from_armored(FIXTURE_SIG).unwrap();
+
+        assert_eq!(sshsig.namespace, "file");
+        assert_eq!(sshsig.hash, SshsigHash::Sha512);
+        assert_eq!(sshsig.public_key, fixture_public_key());
+        assert_eq!(
+            sshsig.fingerprint().unwrap(),
+            "SHA256:CjMwapQFxl8KoWyT/lkR7uznzVrRSgB0/6Fl10/Y4Hw"
+        );
+        assert!(sshsig.verify(FIXTURE_MESSAGE));
+        assert!(!sshsig.verify(b"Freemason sshsig fixture"));
+
+        let wrong_namespace = Sshsig {
+            namespace: "git".to_string(),
+            ..sshsig
+        };
+        assert!(!wrong_namespace.verify(FIXTURE_MESSAGE));
+    }
+
+    #[test]
+    fn ssh_keygen_signature_rearmors_to_same_blob() {
+        let armored = Sshsig::from_armored(FIXTURE_SIG)
+            .unwrap()
+            .to_armored()
+            .unwrap();
+
+        assert_eq!(
+            decode_pem(&armored, SSHSIG_PEM_LABEL),
+            decode_pem(FIXTURE_SIG, SSHSIG_PEM_LABEL)
+        );
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let (public_key, secret_key) = sign_ed25519::gen_keypair();
+        let message = b"round trip";
+        let data = signed_data("file", SshsigHash::Sha256, message);
+        let sshsig = Sshsig {
+            public_key: public_key.as_ref().to_vec(),
+            namespace: "file".to_string(),
+            hash: SshsigHash::Sha256,
+            signature: sign_ed25519::sign_detached(&data, &secret_key)
+                .as_ref()
+                .to_vec(),
+        };
+
+        let decoded = Sshsig::from_armored(&sshsig.to_armored().unwrap()).unwrap();
+        assert_eq!(decoded.public_key, sshsig.public_key);
+        assert_eq!(decoded.namespace, "file");
+        assert_eq!(decoded.hash, SshsigHash::Sha256);
+        assert_eq!(decoded.signature, sshsig.signature);
+        assert!(decoded.verify(message));
+        assert!(!decoded.verify(b"another message"));
+    }
+
+    #[test]
+    fn malformed_signatures_are_rejected() {
+        let blob = decode_pem(FIXTURE_SIG, SSHSIG_PEM_LABEL).unwrap();
+
+        let mut wrong_version = blob.clone();
+        wrong_version[SSHSIG_MAGIC.len() + 3] = 2;
+        let mut trailing = blob.clone();
+        trailing.push(0);
+
+        for blob in [wrong_version, trailing, blob[..blob.len() - 1].to_vec()] {
+            assert!(Sshsig::from_armored(&encode_pem(&blob, SSHSIG_PEM_LABEL)).is_none());
+        }
+        assert!(
+            Sshsig::from_armored(&FIXTURE_SIG.replace("SSH SIGNATURE", "PUBLIC KEY")).is_none()
+        );
+    }
+
+    #[test]
+    fn allowed_signers_options() {
+        let list = format!(
+            "# Comment and blank lines are skipped\n\
+             \n\
+             \"alice@example.com,bob@example.com\" {key}\n\
+             carol@example.com namespaces=\"git,file\" {key}\n\
+             dave@example.com valid-after=\"20240101\",valid-before=\"20250101Z\" {key}\n\
+             *@example.com cert-authority {key}\n\
+             eve@example.com ssh-rsa AAAAB3NzaC1yc2E=\n",
+            key = FIXTURE_KEY
+        );
+        let public_key = fixture_public_key();
+
+        let signers = parse_allowed_signers(&list).unwrap();
+        assert_eq!(signers.len(), 3);
+
+        let (alice, carol, dave) = (&signers[0], &signers[1], &signers[2]);
+        assert!(alice.matches_principal("alice@example.com"));
+        assert!(alice.matches_principal("bob@example.com"));
+        assert!(!alice.matches_principal("carol@example.com"));
+        assert!(alice.allows(&public_key, "anything", 0));
+        assert!(!alice.allows(&[0; 32], "anything", 0));
+
+        assert_eq!(
+            carol.namespaces,
+            Some(vec!["git".to_string(), "file".to_string()])
+        );
+        assert!(carol.allows(&public_key, "git", 0));
+        assert!(carol.allows(&public_key, "file", 0));
+        assert!(!carol.allows(&public_key, "email", 0));
+
+        assert_eq!(dave.valid_after, Some(1_704_067_200));
+        assert_eq!(dave.valid_before, Some(1_735_689_600));
+        assert!(!dave.allows(&public_key, "file", 1_704_067_199));
+        assert!(dave.allows(&public_key, "file", 1_704_067_200));
+        assert!(!dave.allows(&public_key, "file", 1_735_689_600));
+    }
+
+    #[test]
+    fn allowed_signers_reports_malformed_line() {
+        let list = format!(
+            "alice@example.com {key}\n\
+             bob@example.com ssh-ed25519\n",
+            key = FIXTURE_KEY
+        );
+        assert_eq!(parse_allowed_signers(&list).unwrap_err(), 2);
+
+        let list = format!("alice@example.com unknown-option {}\n", FIXTURE_KEY);
+        assert_eq!(parse_allowed_signers(&list).unwrap_err(), 1);
+
+        let list = format!("\"alice@example.com {}\n", FIXTURE_KEY);
+        assert_eq!(parse_allowed_signers(&list).unwrap_err(), 1);
+    }
+
+    #[test]
+    fn ssh_time_known_timestamps() {
+        assert_eq!(parse_ssh_time("19700101"), Some(0));
+        assert_eq!(parse_ssh_time("20240229"), Some(1_709_164_800));
+        assert_eq!(parse_ssh_time("203801190314"), Some(2_147_483_640));
+        assert_eq!(parse_ssh_time("20380119031408Z"), Some(2_147_483_648));
+        assert_eq!(parse_ssh_time("21000301123045"), Some(4_107_587_445));
+
+        assert_eq!(parse_ssh_time("20241301"), None);
+        assert_eq!(parse_ssh_time("20240132"), None);
+        assert_eq!(parse_ssh_time("202402291"), None);
+        assert_eq!(parse_ssh_time("20240229240000"), None);
+        assert_eq!(parse_ssh_time("19691231"), None);
+        assert_eq!(parse_ssh_time("2024-02-29"), None);
+    }
+}
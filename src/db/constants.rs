@@ -2,8 +2,37 @@ use std::num::NonZeroU32;
 
 pub const SIG_COLLECTION: &str = "signatures";
 pub const SIG_ID_COLLECTION: &str = "sig_ids";
+pub const SERIAL_COLLECTION: &str = "serials";
+pub const MASTER_SEED_COLLECTION: &str = "master_seed";
+pub const MASTER_SEED_ID: &str = "freemason_master_seed";
+pub const POLICY_COLLECTION: &str = "policies";
 pub const SIG_TTL: u32 = 3600;
 pub const SECRET_COLLECTION: &str = "secrets";
+pub const SHARE_COLLECTION: &str = "shares";
+pub const BURN_COLLECTION: &str = "burn_secrets";
+pub const INLINE_COLLECTION: &str = "inline_objects";
+pub const LOG_ENTRY_COLLECTION: &str = "log_entries";
+pub const LOG_NODE_COLLECTION: &str = "log_nodes";
+pub const LOG_SIZE_KEY: &str = "log_size";
+pub const SHARE_KEY_ID: &str = "freemason_share_key";
+pub const TIMESTAMP_KEY_ID: &str = "freemason_timestamp_key";
+pub const TIMESTAMP_TOKEN_TYPE: &str = "tst+jwt";
+pub const LOG_KEY_ID: &str = "freemason_log_key";
+
+pub const RETENTION_SWEEP_INTERVAL: u64 = 60;
+pub const JWKS_GRACE_PERIOD: u64 = 7 * 24 * 3600;
+pub const KEY_EXPIRY_GRACE_PERIOD: u64 = 7 * 24 * 3600;
+pub const MAX_KEY_LIFETIME: u64 = 100 * 365 * 24 * 3600;
+pub const KEY_SWEEP_INTERVAL: u64 = 300;
+pub const JWKS_MAX_AGE: u64 = 300;
+
+pub const CHUNK_SIZE: usize = 2 * 1024 * 1024;
+pub const MAX_BURN_SECRET_LEN: u64 = 64 * 1024;
+pub const MAX_BATCH_ITEMS: usize = 1000;
+pub const SEALED_CHUNK_PREFIX_LEN: usize = 4; // Big-endian length of each stored sealed chunk
+pub const UNFRAMED_CHUNK_LEN: usize = CHUNK_SIZE + 16; // Chunk plus its Poly1305 tag
+pub const ZSTD_LEVEL: i32 = 3;
+pub const INLINE_THRESHOLD: usize = 64 * 1024;
 
 pub const PBKDF2_ITERATIONS: Option<NonZeroU32> = NonZeroU32::new(100_000);
 pub const SALT_BASE: [u8; 16] = [
@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::merkle::{self, Hash, NodeStore};
+use crate::crypto::sha3_256;
+use crate::crypto::signing::SigningAlgorithm;
+use crate::db::constants::{LOG_ENTRY_COLLECTION, LOG_NODE_COLLECTION, LOG_SIZE_KEY};
+use crate::db::{open_db, DbError};
+
+/// Record of one issued signature. The JSON serialization of an entry is the leaf
+/// input of its Merkle tree leaf
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub key_id: String,
+    pub key_version: u32,
+    pub algorithm: SigningAlgorithm,
+    pub message_digest: String,
+    pub signature: String,
+    pub timestamp: u64,
+}
+
+impl LogEntry {
+    /// Creates a log entry for an issued signature
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_id` - ID of the signing keypair
+    /// * `key_version` - Version of the signing keypair
+    /// * `algorithm` - Signature algorithm
+    /// * `signature` - Issued signature
+    /// * `message` - Signed message, of which only the digest is logged
+    /// * `timestamp` - UNIX time the signature was issued
+    pub fn new(
+        key_id: &str,
+        key_version: u32,
+        algorithm: SigningAlgorithm,
+        signature: &[u8],
+        message: &[u8],
+        timestamp: u64,
+    ) -> LogEntry {
+        LogEntry {
+            key_id: key_id.to_string(),
+            key_version,
+            algorithm,
+            message_digest: hex::encode(sha3_256::digest(message)),
+            signature: hex::encode(signature),
+            timestamp,
+        }
+    }
+}
+
+/// Append-only Merkle tree log of issued signatures, following RFC 6962 but with
+/// SHA3-256 as the hash function
+#[derive(Debug, Clone)]
+pub struct LogDb {
+    url: String,
+}
+
+/// Node store backed by the sled tree of perfect subtree hashes
+struct SledNodes(sled::Tree);
+
+impl NodeStore for SledNodes {
+    fn node(&self, level: u8, index: u64) -> Option<Hash> {
+        let hash = self.0.get(node_key(level, index)).ok()??;
+        hash.as_ref().try_into().ok()
+    }
+}
+
+impl LogDb {
+    /// Creates a new transparency log database
+    ///
+    /// ### Arguments
+    ///
+    /// * `url` - Database URL
+    pub fn new(url: String) -> LogDb {
+        LogDb { url }
+    }
+
+    /// Appends entries to the log and returns their leaf indices
+    ///
+    /// ### Arguments
+    ///
+    /// * `entries` - Entries to append, in order
+    pub async fn append_entries(&self, entries: Vec<LogEntry>) -> Result<Vec<u64>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+        let (log_entries, nodes) = open_trees(&db)?;
+        let first = read_size(&db)?;
+
+        let mut indices = Vec::with_capacity(entries.len());
+        for (size, entry) in (first..).zip(entries) {
+            let leaf_input = serde_json::to_vec(&entry).unwrap();
+            let new_nodes = match merkle::append_nodes(&nodes, size, merkle::leaf_hash(&leaf_input))
+            {
+                Some(new_nodes) => new_nodes,
+                None => {
+                    return Err(DbError {
+                        message: "Failed to read log tree".to_string(),
+                    });
+                }
+            };
+
+            let mut batch = sled::Batch::default();
+            for (level, index, hash) in new_nodes {
+                batch.insert(node_key(level, index), &hash[..]);
+            }
+            let stored = log_entries
+                .insert(size.to_be_bytes(), leaf_input)
+                .and_then(|_| nodes.0.apply_batch(batch))
+                .and_then(|_| db.insert(LOG_SIZE_KEY, &(size + 1).to_be_bytes()));
+            if stored.is_err() {
+                return Err(DbError {
+                    message: "Failed to append log entry".to_string(),
+                });
+            }
+
+            indices.push(size);
+        }
+
+        Ok(indices)
+    }
+
+    /// Gets the number of entries in the log
+    ///
+    pub async fn tree_size(&self) -> Result<u64, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        read_size(&db)
+    }
+
+    /// Gets the leaf input of the entry at an index
+    ///
+    /// ### Arguments
+    ///
+    /// * `index` - Leaf index of the entry
+    pub async fn get_entry(&self, index: u64) -> Result<Option<Vec<u8>>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+        let (log_entries, _) = open_trees(&db)?;
+
+        match log_entries.get(index.to_be_bytes()) {
+            Ok(entry) => Ok(entry.map(|entry| entry.to_vec())),
+            Err(_) => Err(DbError {
+                message: "Failed to get log entry".to_string(),
+            }),
+        }
+    }
+
+    /// Gets the root hash of the tree of the first `size` entries. Returns None if the
+    /// log is smaller than `size`
+    ///
+    /// ### Arguments
+    ///
+    /// * `size` - Tree size
+    pub async fn root_hash(&self, size: u64) -> Result<Option<Hash>, DbError> {
+        self.with_nodes(size, |nodes| merkle::root_hash(nodes, size))
+            .await
+    }
+
+    /// Gets the audit path of an entry in the tree of the first `size` entries.
+    /// Returns None if the entry is not in that tree or the log is smaller than `size`
+    ///
+    /// ### Arguments
+    ///
+    /// * `index` - Leaf index of the entry
+    /// * `size` - Tree size
+    pub async fn inclusion_proof(
+        &self,
+        index: u64,
+        size: u64,
+    ) -> Result<Option<Vec<Hash>>, DbError> {
+        if index >= size {
+            return Ok(None);
+        }
+
+        self.with_nodes(size, |nodes| merkle::inclusion_proof(nodes, index, size))
+            .await
+    }
+
+    /// Gets the proof that the tree of the first `first` entries is a prefix of the
+    /// tree of the first `second`. Returns None if the sizes are not `0 < first <=
+    /// second` or the log is smaller than `second`
+    ///
+    /// ### Arguments
+    ///
+    /// * `first` - Size of the older tree
+    /// * `second` - Size of the newer tree
+    pub async fn consistency_proof(
+        &self,
+        first: u64,
+        second: u64,
+    ) -> Result<Option<Vec<Hash>>, DbError> {
+        if first == 0 || first > second {
+            return Ok(None);
+        }
+
+        self.with_nodes(second, |nodes| {
+            merkle::consistency_proof(nodes, first, second)
+        })
+        .await
+    }
+
+    /// Runs a tree computation over the stored nodes, if the log holds at least `size`
+    /// entries
+    async fn with_nodes<T>(
+        &self,
+        size: u64,
+        compute: impl FnOnce(&SledNodes) -> Option<T>,
+    ) -> Result<Option<T>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+        if read_size(&db)? < size {
+            return Ok(None);
+        }
+
+        let (_, nodes) = open_trees(&db)?;
+        match compute(&nodes) {
+            Some(result) => Ok(Some(result)),
+            None => Err(DbError {
+                message: "Failed to read log tree".to_string(),
+            }),
+        }
+    }
+}
+
+/// Opens the entry and node trees of the log
+fn open_trees(db: &sled::Db) -> Result<(sled::Tree, SledNodes), DbError> {
+    match (
+        db.open_tree(LOG_ENTRY_COLLECTION),
+        db.open_tree(LOG_NODE_COLLECTION),
+    ) {
+        (Ok(log_entries), Ok(nodes)) => Ok((log_entries, SledNodes(nodes))),
+        _ => Err(DbError {
+            message: "Failed to open log collections".to_string(),
+        }),
+    }
+}
+
+/// Reads the number of entries in the log. The size is only bumped once an entry and
+/// its nodes are stored, so an interrupted append is simply overwritten by the next
+fn read_size(db: &sled::Db) -> Result<u64, DbError> {
+    match db.get(LOG_SIZE_KEY) {
+        Ok(Some(size)) => Ok(size.as_ref().try_into().map_or(0, u64::from_be_bytes)),
+        Ok(None) => Ok(0),
+        Err(_) => Err(DbError {
+            message: "Failed to get log size".to_string(),
+        }),
+    }
+}
+
+/// Key of a stored subtree hash
+fn node_key(level: u8, index: u64) -> Vec<u8> {
+    let mut key = vec![level];
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// Bytes signed in a signed tree head, laid out like the RFC 6962 `TreeHeadSignature`:
+/// version (0) and signature type (1) bytes, then the timestamp in UNIX seconds and
+/// the tree size as big-endian u64s, then the 32 byte SHA3-256 root hash
+///
+/// ### Arguments
+///
+/// * `timestamp` - Time the tree head was signed
+/// * `tree_size` - Number of entries in the tree
+/// * `root_hash` - Root hash of the tree
+pub fn tree_head_input(timestamp: u64, tree_size: u64, root_hash: &Hash) -> Vec<u8> {
+    let mut input = vec![0, 1];
+    input.extend_from_slice(&timestamp.to_be_bytes());
+    input.extend_from_slice(&tree_size.to_be_bytes());
+    input.extend_from_slice(root_hash);
+    input
+}
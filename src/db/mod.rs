@@ -1,8 +1,11 @@
 pub mod constants;
+pub mod log_db;
 pub mod secret_db;
 pub mod security;
 pub mod sign_db;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DbError {
@@ -10,3 +13,23 @@ pub struct DbError {
 }
 
 impl warp::reject::Reject for DbError {}
+
+/// Opens the sled database at a URL, reusing the handle if it is already open.
+/// sled keeps its lock on a database until the flusher thread of a dropped handle
+/// exits, so opening it again straight after a drop can fail
+///
+/// ### Arguments
+///
+/// * `url` - Database URL
+pub fn open_db(url: &str) -> sled::Result<sled::Db> {
+    static OPEN_DBS: OnceLock<Mutex<HashMap<String, sled::Db>>> = OnceLock::new();
+
+    let mut open_dbs = OPEN_DBS.get_or_init(Default::default).lock().unwrap();
+    if let Some(db) = open_dbs.get(url) {
+        return Ok(db.clone());
+    }
+
+    let db = sled::open(url)?;
+    open_dbs.insert(url.to_string(), db.clone());
+    Ok(db)
+}
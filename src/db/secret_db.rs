@@ -1,8 +1,60 @@
 use serde::{Deserialize, Serialize};
 
-use crate::crypto::secretbox_chacha20_poly1305::{Key, Nonce};
+use crate::crypto::secretbox_chacha20_poly1305::{open, seal, Key, Nonce};
+use crate::crypto::signing::SigningAlgorithm;
+use crate::db::constants::{BURN_COLLECTION, INLINE_COLLECTION, SHARE_COLLECTION, ZSTD_LEVEL};
 use crate::db::security::SecurityAtRest;
-use crate::db::DbError;
+use crate::db::{open_db, DbError};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// Compression applied to each chunk before it is sealed. Off unless an upload asks
+/// for it, see the README for why
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl Compression {
+    /// Compresses a plaintext chunk
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Plaintext chunk
+    pub fn compress(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        match self {
+            Compression::None => Some(data),
+            Compression::Zstd => zstd::encode_all(data.as_slice(), ZSTD_LEVEL).ok(),
+        }
+    }
+
+    /// Decompresses a decrypted chunk
+    ///
+    /// ### Arguments
+    ///
+    /// * `data` - Decrypted chunk
+    pub fn decompress(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        match self {
+            Compression::None => Some(data),
+            Compression::Zstd => zstd::decode_all(data.as_slice()).ok(),
+        }
+    }
+}
+
+/// Layout of the stored ciphertext of a secret entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CiphertextFormat {
+    /// Sealed chunks stored back to back under the entry's key and nonce, as written
+    /// before chunks were framed. Entries stored without a format use this
+    #[default]
+    Unframed,
+    /// Length-prefixed chunks, each sealed with a nonce derived from its offset
+    Framed,
+}
 
 /// Full data for handling a secret key entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +63,41 @@ pub struct SecretEntry {
     pub total_chunks: usize,
     pub key: Vec<u8>,
     pub nonce: Vec<u8>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub legal_hold: bool,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub inline: bool,
+    #[serde(default)]
+    pub signature: Option<FileSignature>,
+    #[serde(default)]
+    pub format: CiphertextFormat,
+}
+
+impl SecretEntry {
+    /// Whether the entry is past its expiry time and not under legal hold, so is
+    /// due to be shredded
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn is_expired(&self, now: u64) -> bool {
+        !self.legal_hold && self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Detached signature over the SHA3-256 digest of a stored file's plaintext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSignature {
+    pub key_id: String,
+    pub key_version: u32,
+    pub algorithm: SigningAlgorithm,
+    pub digest: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub signed_at: u64,
 }
 
 /// Secret key entry with key and nonce
@@ -20,6 +107,40 @@ pub struct SecretEntryWithKeyAndNonce {
     pub total_chunks: usize,
     pub key: Key,
     pub nonce: Nonce,
+    pub expires_at: Option<u64>,
+    pub legal_hold: bool,
+    pub compression: Compression,
+    pub inline: bool,
+    pub format: CiphertextFormat,
+}
+
+impl SecretEntryWithKeyAndNonce {
+    /// Whether the entry is past its expiry time and not under legal hold, so can
+    /// no longer be read
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn is_expired(&self, now: u64) -> bool {
+        !self.legal_hold && self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// One-time secret, removed from the database on its first successful read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurnEntry {
+    pub cipher_text: Vec<u8>,
+    pub key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub expires_at: Option<u64>,
+}
+
+/// Outcome of an attempt to read a one-time secret
+#[derive(Debug, Clone)]
+pub enum BurnRead {
+    Revealed(Vec<u8>),
+    Gone,
+    Denied,
 }
 
 /// Secret key database
@@ -48,7 +169,7 @@ impl SecretDb {
     ///
     /// * `secret_entry` - Secret entry to insert
     pub async fn insert_secret(&self, secret_entry: SecretEntry) -> Result<(), DbError> {
-        let db = match sled::open(self.url.clone()) {
+        let db = match open_db(&self.url) {
             Ok(db) => db,
             Err(_) => {
                 return Err(DbError {
@@ -79,7 +200,7 @@ impl SecretDb {
         id: &str,
         passphrase: &str,
     ) -> Result<SecretEntryWithKeyAndNonce, DbError> {
-        let db = match sled::open(self.url.clone()) {
+        let db = match open_db(&self.url) {
             Ok(db) => db,
             Err(_) => {
                 return Err(DbError {
@@ -103,20 +224,296 @@ impl SecretDb {
         };
 
         let rest_key = self.security.derive_rest_key(id, passphrase);
-        let (key, nonce) = self.security.decrypt_key_and_nonce_for_storage(
+        let (key, nonce) = match self.security.decrypt_key_and_nonce_for_storage(
             rest_key,
             secret_entry.key,
             secret_entry.nonce,
-        );
+        ) {
+            Some(key_and_nonce) => key_and_nonce,
+            None => {
+                return Err(DbError {
+                    message: "Failed to decrypt secret key".to_string(),
+                });
+            }
+        };
 
         Ok(SecretEntryWithKeyAndNonce {
             file_name: secret_entry.file_name,
             total_chunks: secret_entry.total_chunks,
             key,
             nonce,
+            expires_at: secret_entry.expires_at,
+            legal_hold: secret_entry.legal_hold,
+            compression: secret_entry.compression,
+            inline: secret_entry.inline,
+            format: secret_entry.format,
         })
     }
 
+    /// Gets the stored metadata of a secret entry, without decrypting its key
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    pub async fn get_secret_entry(&self, id: &str) -> Result<Option<SecretEntry>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db.get(id) {
+            Ok(Some(entry)) => Ok(Some(serde_json::from_slice(&entry).unwrap())),
+            Ok(None) => Ok(None),
+            Err(_) => Err(DbError {
+                message: "Failed to find secret data".to_string(),
+            }),
+        }
+    }
+
+    /// Stores a small ciphertext directly in the database instead of on the filesystem
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry the ciphertext belongs to
+    /// * `cipher_text` - Encrypted data
+    pub async fn insert_inline_object(
+        &self,
+        id: &str,
+        cipher_text: Vec<u8>,
+    ) -> Result<(), DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db
+            .open_tree(INLINE_COLLECTION)
+            .and_then(|inline_objects| inline_objects.insert(id, cipher_text))
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(DbError {
+                message: "Failed to insert inline object".to_string(),
+            }),
+        }
+    }
+
+    /// Removes the ciphertext of a secret entry, leaving the entry itself in place
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    /// * `inline` - Whether the ciphertext is stored inline in the database
+    pub async fn remove_ciphertext(&self, id: &str, inline: bool) -> Result<(), DbError> {
+        if !inline {
+            return match std::fs::remove_file(id) {
+                Ok(_) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(_) => Err(DbError {
+                    message: "Failed to remove encrypted file".to_string(),
+                }),
+            };
+        }
+
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db
+            .open_tree(INLINE_COLLECTION)
+            .and_then(|inline_objects| inline_objects.remove(id))
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(DbError {
+                message: "Failed to remove inline object".to_string(),
+            }),
+        }
+    }
+
+    /// Opens the ciphertext of a secret entry for reading, wherever it is stored
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    /// * `inline` - Whether the ciphertext is stored inline in the database
+    /// * `offset` - Byte offset to start reading from
+    pub async fn open_ciphertext(
+        &self,
+        id: &str,
+        inline: bool,
+        offset: u64,
+    ) -> Result<Box<dyn Read + Send>, DbError> {
+        if !inline {
+            return match File::open(id).and_then(|mut file| {
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(file)
+            }) {
+                Ok(file) => Ok(Box::new(file)),
+                Err(_) => Err(DbError {
+                    message: "Failed to open encrypted file".to_string(),
+                }),
+            };
+        }
+
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db
+            .open_tree(INLINE_COLLECTION)
+            .and_then(|inline_objects| inline_objects.get(id))
+        {
+            Ok(Some(cipher_text)) => {
+                let mut reader = Cursor::new(cipher_text.to_vec());
+                reader.set_position(offset);
+                Ok(Box::new(reader))
+            }
+            _ => Err(DbError {
+                message: "Failed to find inline object".to_string(),
+            }),
+        }
+    }
+
+    /// Places or lifts a legal hold on a secret entry. Held entries are never
+    /// expired or deleted
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    /// * `legal_hold` - Whether the entry should be held
+    pub async fn set_legal_hold(&self, id: &str, legal_hold: bool) -> Result<(), DbError> {
+        let mut secret_entry = match self.get_secret_entry(id).await? {
+            Some(entry) => entry,
+            None => {
+                return Err(DbError {
+                    message: "Failed to find secret data".to_string(),
+                });
+            }
+        };
+
+        secret_entry.legal_hold = legal_hold;
+        self.insert_secret(secret_entry).await
+    }
+
+    /// Stores the detached signature of a secret entry's plaintext, replacing any
+    /// previous one
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    /// * `signature` - Signature over the plaintext digest
+    pub async fn set_file_signature(
+        &self,
+        id: &str,
+        signature: FileSignature,
+    ) -> Result<(), DbError> {
+        let mut secret_entry = match self.get_secret_entry(id).await? {
+            Some(entry) => entry,
+            None => {
+                return Err(DbError {
+                    message: "Failed to find secret data".to_string(),
+                });
+            }
+        };
+
+        secret_entry.signature = Some(signature);
+        self.insert_secret(secret_entry).await
+    }
+
+    /// Crypto-shreds a secret entry by destroying its key and removing its
+    /// ciphertext file. Does not check for a legal hold
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the secret entry
+    pub async fn shred_secret(&self, id: &str) -> Result<(), DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let removed = db
+            .remove(id)
+            .and_then(|_| db.open_tree(INLINE_COLLECTION))
+            .and_then(|inline_objects| inline_objects.remove(id))
+            .and_then(|_| db.flush());
+        if removed.is_err() {
+            return Err(DbError {
+                message: "Failed to remove secret data".to_string(),
+            });
+        }
+
+        match std::fs::remove_file(id) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(_) => Err(DbError {
+                message: "Failed to remove encrypted file".to_string(),
+            }),
+        }
+    }
+
+    /// Crypto-shreds all expired secret entries that are not under legal hold,
+    /// along with any expired one-time secrets. Returns the IDs of the shredded entries
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub async fn purge_expired(&self, now: u64) -> Result<Vec<String>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let expired: Vec<String> = db
+            .iter()
+            .values()
+            .filter_map(|entry| serde_json::from_slice::<SecretEntry>(&entry.ok()?).ok())
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| entry.file_name)
+            .collect();
+
+        if let Ok(burns) = db.open_tree(BURN_COLLECTION) {
+            for (token, entry) in burns.iter().flatten() {
+                let burn_entry: Option<BurnEntry> = serde_json::from_slice(&entry).ok();
+                if burn_entry.is_some_and(|e| e.expires_at.is_some_and(|at| at <= now)) {
+                    let _ = burns.remove(token);
+                }
+            }
+        }
+        drop(db);
+
+        for id in &expired {
+            self.shred_secret(id).await?;
+        }
+
+        Ok(expired)
+    }
+
     /// Creates a secret entry
     ///
     /// ### Arguments
@@ -125,12 +522,17 @@ impl SecretDb {
     /// * `passphrase` - Passphrase to derive the key from
     /// * `total_chunks` - Total number of chunks
     /// * `key_and_nonce` - Key and nonce to encrypt
+    /// * `expires_at` - Optional UNIX time after which the entry is shredded
+    /// * `storage` - Compression applied to each chunk before sealing, and whether
+    ///   the ciphertext is stored inline in the database
     pub fn create_secret_entry(
         &self,
         id: &str,
         passphrase: &str,
         total_chunks: usize,
         key_and_nonce: (Key, Nonce),
+        expires_at: Option<u64>,
+        storage: (Compression, bool),
     ) -> SecretEntry {
         let rest_key = self.security.derive_rest_key(id, passphrase);
         let (encrypted_key, encrypted_nonce) = self.security.encrypt_key_and_nonce_for_storage(
@@ -144,6 +546,190 @@ impl SecretDb {
             total_chunks,
             key: encrypted_key,
             nonce: encrypted_nonce,
+            expires_at,
+            legal_hold: false,
+            compression: storage.0,
+            inline: storage.1,
+            signature: None,
+            format: CiphertextFormat::Framed,
         }
     }
+
+    /// Records a download against a share link, returning whether the download
+    /// is still within the link's allowance
+    ///
+    /// ### Arguments
+    ///
+    /// * `share_id` - ID of the share link
+    /// * `max_downloads` - Maximum number of downloads allowed for the link
+    pub async fn record_share_download(
+        &self,
+        share_id: &str,
+        max_downloads: Option<u32>,
+    ) -> Result<bool, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let shares = match db.open_tree(SHARE_COLLECTION) {
+            Ok(tree) => tree,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open share collection".to_string(),
+                });
+            }
+        };
+
+        let max = max_downloads.unwrap_or(u32::MAX);
+        let previous = shares.fetch_and_update(share_id, |count| {
+            let count = count.map_or(0, decode_count);
+            if count < max {
+                Some((count + 1).to_be_bytes().to_vec())
+            } else {
+                Some(count.to_be_bytes().to_vec())
+            }
+        });
+
+        match previous {
+            Ok(previous) => Ok(previous.as_deref().map_or(0, decode_count) < max),
+            Err(_) => Err(DbError {
+                message: "Failed to record share download".to_string(),
+            }),
+        }
+    }
+
+    /// Encrypts and stores a one-time secret
+    ///
+    /// ### Arguments
+    ///
+    /// * `token` - Retrieval token of the secret
+    /// * `passphrase` - Passphrase to derive the key from
+    /// * `secret` - Plaintext of the secret
+    /// * `expires_at` - Optional UNIX time after which the secret can no longer be read
+    pub async fn insert_burn_secret(
+        &self,
+        token: &str,
+        passphrase: &str,
+        secret: Vec<u8>,
+        expires_at: Option<u64>,
+    ) -> Result<(), DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let key = Key::new();
+        let nonce = Nonce::new();
+        let cipher_text = seal(secret, &nonce, &key).unwrap();
+        let rest_key = self.security.derive_rest_key(token, passphrase);
+        let (encrypted_key, encrypted_nonce) = self
+            .security
+            .encrypt_key_and_nonce_for_storage(rest_key, key, nonce);
+
+        let burn_entry = BurnEntry {
+            cipher_text,
+            key: encrypted_key,
+            nonce: encrypted_nonce,
+            expires_at,
+        };
+        let burn_entry = serde_json::to_vec(&burn_entry).unwrap();
+
+        match db
+            .open_tree(BURN_COLLECTION)
+            .and_then(|burns| burns.insert(token, burn_entry))
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(DbError {
+                message: "Failed to insert one-time secret".to_string(),
+            }),
+        }
+    }
+
+    /// Reads a one-time secret, atomically removing it from the database
+    ///
+    /// ### Arguments
+    ///
+    /// * `token` - Retrieval token of the secret
+    /// * `passphrase` - Passphrase to derive the key from
+    /// * `now` - Current UNIX time
+    pub async fn take_burn_secret(
+        &self,
+        token: &str,
+        passphrase: &str,
+        now: u64,
+    ) -> Result<BurnRead, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let burns = match db.open_tree(BURN_COLLECTION) {
+            Ok(tree) => tree,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open one-time secret collection".to_string(),
+                });
+            }
+        };
+
+        let raw_entry = match burns.get(token) {
+            Ok(Some(raw_entry)) => raw_entry,
+            Ok(None) => return Ok(BurnRead::Gone),
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to find one-time secret".to_string(),
+                });
+            }
+        };
+        let burn_entry: BurnEntry = serde_json::from_slice(&raw_entry).unwrap();
+
+        if burn_entry
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            let _ = burns.remove(token);
+            return Ok(BurnRead::Gone);
+        }
+
+        let rest_key = self.security.derive_rest_key(token, passphrase);
+        let secret = self
+            .security
+            .decrypt_key_and_nonce_for_storage(rest_key, burn_entry.key, burn_entry.nonce)
+            .and_then(|(key, nonce)| open(burn_entry.cipher_text, &nonce, &key));
+        let secret = match secret {
+            Some(secret) => secret,
+            None => return Ok(BurnRead::Denied),
+        };
+
+        // Only the reader that actually removes the entry gets to see the plaintext
+        match burns.compare_and_swap(token, Some(raw_entry), None as Option<&[u8]>) {
+            Ok(Ok(())) => Ok(BurnRead::Revealed(secret)),
+            Ok(Err(_)) => Ok(BurnRead::Gone),
+            Err(_) => Err(DbError {
+                message: "Failed to remove one-time secret".to_string(),
+            }),
+        }
+    }
+}
+
+/// Decodes a big-endian download counter
+///
+/// ### Arguments
+///
+/// * `raw` - Raw counter bytes
+fn decode_count(raw: &[u8]) -> u32 {
+    raw.try_into().map(u32::from_be_bytes).unwrap_or(u32::MAX)
 }
@@ -1,6 +1,5 @@
 use super::constants::{PBKDF2_ITERATIONS, SALT_BASE};
-use crate::crypto::secretbox_chacha20_poly1305::{open, seal, Key, Nonce};
-use crate::crypto::sign_ed25519::{PublicKey, SecretKey};
+use crate::crypto::secretbox_chacha20_poly1305::{open, seal, Key, Nonce, NONCE_LEN};
 use crate::crypto::utils::generate_nonce;
 use std::num::NonZeroU32;
 
@@ -16,6 +15,12 @@ pub struct SecurityAtRest {
     pub salt_component: [u8; 16],
 }
 
+impl Default for SecurityAtRest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SecurityAtRest {
     /// Creates a new security at rest instance, with all sensible, secure defaults
     pub fn new() -> Self {
@@ -35,7 +40,7 @@ impl SecurityAtRest {
     /// * `id` - ID of the salt entry
     /// * `salt_component` - Base salt component
     fn generate_salt(&self, id: &str) -> Vec<u8> {
-        let mut salt = Vec::with_capacity(self.salt_component.len() + id.as_bytes().len());
+        let mut salt = Vec::with_capacity(self.salt_component.len() + id.len());
         salt.extend(self.salt_component.as_ref());
         salt.extend(id.as_bytes());
         salt
@@ -102,64 +107,110 @@ impl SecurityAtRest {
         rest_key: [u8; CREDENTIAL_LEN],
         key: Vec<u8>,
         nonce: Vec<u8>,
-    ) -> (Key, Nonce) {
-        let decrypted_key = open(key, &self.nonce, &Key::from_slice(&rest_key).unwrap());
-        let decrypted_nonce = open(nonce, &self.nonce, &Key::from_slice(&rest_key).unwrap());
+    ) -> Option<(Key, Nonce)> {
+        let decrypted_key = open(key, &self.nonce, &Key::from_slice(&rest_key).unwrap())?;
+        let decrypted_nonce = open(nonce, &self.nonce, &Key::from_slice(&rest_key).unwrap())?;
+
+        Some((
+            Key::from_slice(&decrypted_key)?,
+            Nonce::from_slice(&decrypted_nonce)?,
+        ))
+    }
 
+    /// Encrypts a keypair for storage, each key under a nonce of its own
+    ///
+    /// ### Arguments
+    ///
+    /// * `rest_key` - Rest key
+    /// * `keypair` - Key pair to encrypt
+    pub fn encrypt_keys_for_storage<P: AsRef<[u8]>, S: AsRef<[u8]>>(
+        &self,
+        rest_key: [u8; CREDENTIAL_LEN],
+        keypair: (P, S),
+    ) -> (Vec<u8>, Vec<u8>) {
         (
-            Key::from_slice(&decrypted_key.unwrap()).unwrap(),
-            Nonce::from_slice(&decrypted_nonce.unwrap()).unwrap(),
+            self.seal_for_storage(rest_key, keypair.0.as_ref()),
+            self.seal_for_storage(rest_key, keypair.1.as_ref()),
         )
     }
 
-    /// Encrypts a keypair for storage
+    /// Encrypts a master seed for storage
     ///
     /// ### Arguments
     ///
     /// * `rest_key` - Rest key
-    /// * `nonce` - Nonce
-    /// * `keypair` - Key pair to encrypt
-    pub fn encrypt_keys_for_storage(
+    /// * `seed` - Seed to encrypt
+    pub fn encrypt_seed_for_storage(&self, rest_key: [u8; CREDENTIAL_LEN], seed: &[u8]) -> Vec<u8> {
+        self.seal_for_storage(rest_key, seed)
+    }
+
+    /// Decrypts a master seed from storage
+    ///
+    /// ### Arguments
+    ///
+    /// * `rest_key` - Rest key
+    /// * `seed` - Seed to decrypt
+    pub fn decrypt_seed_from_storage(
         &self,
         rest_key: [u8; CREDENTIAL_LEN],
-        keypair: (PublicKey, SecretKey),
-    ) -> (Vec<u8>, Vec<u8>) {
-        let pub_key = seal(
-            keypair.0.as_ref().to_vec(),
-            &self.nonce,
-            &Key::from_slice(&rest_key).unwrap(),
-        );
-        let secret_key = seal(
-            keypair.1.as_ref().to_vec(),
-            &self.nonce,
-            &Key::from_slice(&rest_key).unwrap(),
-        );
+        seed: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        self.open_from_storage(rest_key, seed)
+    }
+
+    /// Encrypts a value for storage under a fresh random nonce, which is stored in
+    /// front of the ciphertext so that the value can be decrypted after a restart
+    ///
+    /// ### Arguments
+    ///
+    /// * `rest_key` - Rest key
+    /// * `value` - Value to encrypt
+    fn seal_for_storage(&self, rest_key: [u8; CREDENTIAL_LEN], value: &[u8]) -> Vec<u8> {
+        let nonce = Nonce::from_slice(&generate_nonce()).unwrap();
+        let ciphertext = seal(value.to_vec(), &nonce, &Key::from_slice(&rest_key).unwrap());
 
-        (pub_key.unwrap(), secret_key.unwrap())
+        let mut sealed = nonce.as_ref().to_vec();
+        sealed.extend(ciphertext.unwrap());
+        sealed
     }
 
-    /// Decrypts a keypair from storage
+    /// Decrypts a value encrypted by `seal_for_storage`
+    ///
+    /// ### Arguments
+    ///
+    /// * `rest_key` - Rest key
+    /// * `sealed` - Nonce followed by the ciphertext
+    fn open_from_storage(
+        &self,
+        rest_key: [u8; CREDENTIAL_LEN],
+        sealed: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        open(
+            ciphertext.to_vec(),
+            &Nonce::from_slice(nonce)?,
+            &Key::from_slice(&rest_key).unwrap(),
+        )
+    }
+
+    /// Decrypts a keypair from storage, returning the raw public and secret key
     ///
     /// ### Arguments
     ///
     /// * `rest_key` - Rest key
-    /// * `nonce` - Nonce
     /// * `keypair` - Key pair to decrypt
     pub fn decrypt_keys_from_storage(
         &self,
         rest_key: [u8; CREDENTIAL_LEN],
         keypair: (Vec<u8>, Vec<u8>),
-    ) -> Option<(PublicKey, SecretKey)> {
-        let pub_key = open(keypair.0, &self.nonce, &Key::from_slice(&rest_key).unwrap());
-        let secret_key = open(keypair.1, &self.nonce, &Key::from_slice(&rest_key).unwrap());
-
-        if pub_key.is_some() && secret_key.is_some() {
-            return Some((
-                PublicKey::from_slice(&pub_key.unwrap()).unwrap(),
-                SecretKey::from_slice(&secret_key.unwrap()).unwrap(),
-            ));
-        }
+    ) -> Option<(Vec<u8>, Vec<u8>)> {
+        let pub_key = self.open_from_storage(rest_key, keypair.0)?;
+        let secret_key = self.open_from_storage(rest_key, keypair.1)?;
 
-        None
+        Some((pub_key, secret_key))
     }
 }
@@ -1,25 +1,184 @@
 use super::security::SecurityAtRest;
+use crate::crypto::generate_random;
 use crate::crypto::sha3_256;
-use crate::crypto::sign_ed25519::{gen_keypair, PublicKey, Signature};
-use crate::db::constants::SIG_TTL;
-use crate::db::DbError;
+use crate::crypto::sign_ecdsa::SignatureEncoding;
+use crate::crypto::sign_ed25519;
+use crate::crypto::sign_rsa::{self, RSA_DEFAULT_KEY_BITS};
+use crate::crypto::signing::{self, RawKeypair, SigningAlgorithm};
+use crate::crypto::slip10;
+use crate::db::constants::{
+    JWKS_MAX_AGE, KEY_EXPIRY_GRACE_PERIOD, LOG_KEY_ID, MASTER_SEED_COLLECTION, MASTER_SEED_ID,
+    POLICY_COLLECTION, SERIAL_COLLECTION, SHARE_KEY_ID, TIMESTAMP_KEY_ID,
+};
+use crate::db::{open_db, DbError};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Full data for handling a signing, pub/priv keypair
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignatureEntry {
+    #[serde(default)]
+    pub id: String,
     pub pk_hash: String,
     pub pub_key: Vec<u8>,
     pub secret_key: Vec<u8>,
     pub ttl: u32,
     pub timestamp: String,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub disabled_at: Option<u64>,
+    #[serde(default = "first_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub retired_at: Option<u64>,
+    #[serde(default)]
+    pub algorithm: SigningAlgorithm,
+    #[serde(default)]
+    pub not_after: Option<u64>,
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+}
+
+impl SignatureEntry {
+    /// UNIX time the keypair version was created, unknown for keypairs stored before
+    /// creation times were recorded
+    pub fn created_at(&self) -> Option<u64> {
+        self.timestamp.parse().ok()
+    }
+
+    /// Whether the keypair version is past its `not_after` time and can no longer sign
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.not_after.is_some_and(|at| at <= now)
+    }
+
+    /// Whether signatures made with the keypair version still verify. Expired versions
+    /// keep verifying for a grace period, retired versions do not verify at all
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn verifies_at(&self, now: u64) -> bool {
+        self.retired_at.is_none()
+            && self
+                .not_after
+                .is_none_or(|at| now < at.saturating_add(KEY_EXPIRY_GRACE_PERIOD))
+    }
+}
+
+/// Creation time and lifetime of a keypair version
+#[derive(Debug, Clone, Copy)]
+pub struct KeyLifetime {
+    pub created_at: u64,
+    pub ttl: u32,
+    pub not_after: Option<u64>,
+}
+
+impl KeyLifetime {
+    /// Lifetime of a keypair version created now. An explicit `not_after` wins over
+    /// the TTL, and a TTL of 0 means the version never expires
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    /// * `ttl` - Seconds the version can sign for
+    /// * `not_after` - UNIX time after which the version can no longer sign
+    pub fn new(now: u64, ttl: u32, not_after: Option<u64>) -> KeyLifetime {
+        KeyLifetime {
+            created_at: now,
+            ttl,
+            not_after: not_after.or((ttl > 0).then(|| now.saturating_add(ttl as u64))),
+        }
+    }
+
+    /// Lifetime of a keypair version created now that never expires, as used for the
+    /// server's own keys
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn unlimited(now: u64) -> KeyLifetime {
+        KeyLifetime::new(now, 0, None)
+    }
+}
+
+/// Result of signing a message with a stored keypair
+#[derive(Debug, Clone)]
+pub struct SignedMessage {
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub version: u32,
+    pub algorithm: SigningAlgorithm,
 }
 
-/// ID struct for a signature entry
+/// Signer of a verification policy, holding either a raw Ed25519 public key in hex
+/// or the ID of a stored Ed25519 keypair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicySigner {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+}
+
+/// Named rule that a message needs valid signatures from at least `threshold` of
+/// the signers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationPolicy {
+    pub name: String,
+    pub threshold: usize,
+    pub signers: Vec<PolicySigner>,
+    pub created_at: u64,
+}
+
+/// Outcome of checking signatures against a verification policy
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PolicyVerification {
+    pub satisfied: bool,
+    pub valid_signers: Vec<String>,
+    pub invalid_signers: Vec<String>,
+    pub unknown_signers: Vec<String>,
+    pub invalid_signatures: usize,
+}
+
+/// ID struct for a signature entry. `pk_hash` points at the newest version,
+/// `versions` holds the public key hashes of every version, oldest first
 #[derive(Serialize, Deserialize)]
 pub struct SigId {
     pub id: String,
     pub pk_hash: String,
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+/// Version given to the first keypair of an ID
+fn first_version() -> u32 {
+    1
+}
+
+/// Whether an ID belongs to one of the server's own keypairs, which sign share
+/// links, timestamps and log tree heads and so cannot be used or managed by clients
+///
+/// ### Arguments
+///
+/// * `id` - ID of the keypair
+pub fn is_reserved_key_id(id: &str) -> bool {
+    [SHARE_KEY_ID, TIMESTAMP_KEY_ID, LOG_KEY_ID].contains(&id)
+}
+
+/// Published JWKS document, rebuilt only when it is older than `JWKS_MAX_AGE` or
+/// the stored keypairs change. `generation` counts those changes, so that a
+/// document built from keypairs that changed in the meantime is not cached
+#[derive(Debug, Default)]
+struct JwksCache {
+    generation: u64,
+    document: Option<(u64, serde_json::Value)>,
 }
 
 /// Signature database
@@ -27,6 +186,7 @@ pub struct SigId {
 pub struct SignatureDb {
     url: String,
     security: SecurityAtRest,
+    jwks: Arc<std::sync::Mutex<JwksCache>>,
 }
 
 impl SignatureDb {
@@ -39,10 +199,51 @@ impl SignatureDb {
         SignatureDb {
             url,
             security: SecurityAtRest::new(),
+            jwks: Arc::new(std::sync::Mutex::new(JwksCache::default())),
+        }
+    }
+
+    /// Gets the cached JWKS document, if it is still fresh
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub fn cached_jwks(&self, now: u64) -> Option<serde_json::Value> {
+        let cache = self.jwks.lock().unwrap();
+        match &cache.document {
+            Some((built_at, document)) if now < built_at + JWKS_MAX_AGE => Some(document.clone()),
+            _ => None,
+        }
+    }
+
+    /// Number of changes to the stored keypairs so far, to be passed to `cache_jwks`
+    pub fn jwks_generation(&self) -> u64 {
+        self.jwks.lock().unwrap().generation
+    }
+
+    /// Caches a JWKS document, unless the keypairs have changed since it was built
+    ///
+    /// ### Arguments
+    ///
+    /// * `generation` - Value of `jwks_generation` before the keypairs were listed
+    /// * `document` - JWKS document
+    /// * `now` - Current UNIX time
+    pub fn cache_jwks(&self, generation: u64, document: serde_json::Value, now: u64) {
+        let mut cache = self.jwks.lock().unwrap();
+        if cache.generation == generation {
+            cache.document = Some((now, document));
         }
     }
 
-    /// Inserts signature data into the database
+    /// Drops the cached JWKS document after a change to the stored keypairs
+    fn invalidate_jwks(&self) {
+        let mut cache = self.jwks.lock().unwrap();
+        cache.generation += 1;
+        cache.document = None;
+    }
+
+    /// Inserts signature data into the database. Data for a keypair the ID does not
+    /// know yet is added as its newest version
     ///
     /// ### Arguments
     ///
@@ -53,7 +254,7 @@ impl SignatureDb {
         message_id: String,
         signature_data: SignatureEntry,
     ) -> Result<(), DbError> {
-        let db = match sled::open(self.url.clone()) {
+        let db = match open_db(&self.url) {
             Ok(db) => db,
             Err(_) => {
                 return Err(DbError {
@@ -65,10 +266,23 @@ impl SignatureDb {
         println!("Inserting signature data into the collection");
 
         // Inserting the SigId
-        let sig_id_json = serde_json::json!(SigId {
-            id: message_id.clone(),
-            pk_hash: signature_data.pk_hash.clone(),
-        });
+        let mut sig_id = match db.get(&message_id) {
+            Ok(Some(sig_id_raw)) => serde_json::from_slice(&sig_id_raw).unwrap(),
+            _ => SigId {
+                id: message_id.clone(),
+                pk_hash: signature_data.pk_hash.clone(),
+                versions: Vec::new(),
+            },
+        };
+        if sig_id.versions.is_empty() && sig_id.pk_hash != signature_data.pk_hash {
+            sig_id.versions.push(sig_id.pk_hash.clone());
+        }
+        if !sig_id.versions.contains(&signature_data.pk_hash) {
+            sig_id.versions.push(signature_data.pk_hash.clone());
+            sig_id.pk_hash = signature_data.pk_hash.clone();
+        }
+
+        let sig_id_json = serde_json::json!(sig_id);
         let sig_id = serde_json::to_vec(&sig_id_json).unwrap();
         db.insert(message_id, sig_id).unwrap();
 
@@ -78,17 +292,21 @@ impl SignatureDb {
         let sig_data_json = serde_json::json!(signature_data);
         let sig_data = serde_json::to_vec(&sig_data_json).unwrap();
         db.insert(signature_data.pk_hash, sig_data).unwrap();
+        self.invalidate_jwks();
 
         Ok(())
     }
 
-    /// Gets signature data from the database
+    /// Gets signature data from the database, if any exists for the message ID
     ///
     /// ### Arguments
     ///
     /// * `message_id` - Message ID to get signature data for
-    pub async fn get_signature_data(&self, message_id: String) -> Result<SignatureEntry, DbError> {
-        let db = match sled::open(self.url.clone()) {
+    pub async fn get_signature_data(
+        &self,
+        message_id: String,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        let db = match open_db(&self.url) {
             Ok(db) => db,
             Err(e) => {
                 println!("Error: {}", e);
@@ -103,9 +321,7 @@ impl SignatureDb {
             Ok(Some(sig_id_raw)) => serde_json::from_slice(&sig_id_raw).unwrap(),
             Ok(None) => {
                 println!("No value found for key");
-                return Err(DbError {
-                    message: "No value found for key".to_string(),
-                });
+                return Ok(None);
             }
             Err(e) => {
                 println!("Error: {}", e);
@@ -117,116 +333,1208 @@ impl SignatureDb {
 
         match db.get(&sig_id.pk_hash) {
             Ok(Some(sig_data)) => {
-                let sig_data: SignatureEntry = serde_json::from_slice(&sig_data).unwrap();
-                Ok(sig_data)
+                let mut sig_data: SignatureEntry = serde_json::from_slice(&sig_data).unwrap();
+                // Entries written before the ID was recorded only know it through the SigId
+                if sig_data.id.is_empty() {
+                    sig_data.id = sig_id.id;
+                }
+                Ok(Some(sig_data))
             }
             Ok(None) => {
                 println!("No value found for key");
-                return Err(DbError {
-                    message: "No value found for key".to_string(),
-                });
+                Ok(None)
             }
             Err(e) => {
                 println!("Error: {}", e);
+                Err(DbError {
+                    message: "Failed to get value from database".to_string(),
+                })
+            }
+        }
+    }
+
+    /// Gets every version of the signature data of a message ID, oldest first
+    ///
+    /// ### Arguments
+    ///
+    /// * `message_id` - Message ID to get signature data for
+    pub async fn get_key_versions(
+        &self,
+        message_id: String,
+    ) -> Result<Option<Vec<SignatureEntry>>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let sig_id: SigId = match db.get(&message_id) {
+            Ok(Some(sig_id_raw)) => serde_json::from_slice(&sig_id_raw).unwrap(),
+            Ok(None) => return Ok(None),
+            Err(_) => {
                 return Err(DbError {
                     message: "Failed to get value from database".to_string(),
                 });
             }
+        };
+
+        let pk_hashes = match sig_id.versions.is_empty() {
+            true => vec![sig_id.pk_hash],
+            false => sig_id.versions,
+        };
+
+        let mut versions = Vec::with_capacity(pk_hashes.len());
+        for pk_hash in pk_hashes {
+            match db.get(&pk_hash) {
+                Ok(Some(sig_data)) => {
+                    let mut sig_data: SignatureEntry = serde_json::from_slice(&sig_data).unwrap();
+                    if sig_data.id.is_empty() {
+                        sig_data.id = sig_id.id.clone();
+                    }
+                    versions.push(sig_data);
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    return Err(DbError {
+                        message: "Failed to get value from database".to_string(),
+                    });
+                }
+            }
         }
+
+        Ok(Some(versions))
     }
 
-    /// Creates a signature entry
+    /// Creates and stores a new signing keypair for an ID that has none yet
     ///
     /// ### Arguments
     ///
     /// * `id` - ID of the signature entry
     /// * `passphrase` - Passphrase to derive an encryption key from
-    pub fn create_signature_data(&self, id: &str, passphrase: &str) -> SignatureEntry {
-        let keypair = gen_keypair();
-        let pk_hash = hex::encode(sha3_256::digest(keypair.0.as_ref()));
-        let rest_key = self.security.derive_rest_key(id, passphrase);
-        let (pub_key, secret_key) = self.security.encrypt_keys_for_storage(rest_key, keypair);
+    /// * `algorithm` - Signature algorithm of the keypair
+    /// * `rsa_bits` - Modulus size of RSA keys, ignored for other algorithms
+    /// * `lifetime` - Creation time and lifetime of the keypair
+    pub async fn create_key(
+        &self,
+        id: &str,
+        passphrase: &str,
+        algorithm: SigningAlgorithm,
+        rsa_bits: usize,
+        lifetime: KeyLifetime,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        // RSA key generation is slow, so don't bother for an ID that is taken
+        if self.get_signature_data(id.to_string()).await?.is_some() {
+            return Ok(None);
+        }
 
-        SignatureEntry {
-            pk_hash,
-            pub_key,
-            secret_key,
-            ttl: SIG_TTL,
-            timestamp: "".to_string(),
+        let keypair = match signing::gen_keypair(algorithm, rsa_bits) {
+            Some(keypair) => keypair,
+            None => {
+                return Err(DbError {
+                    message: "Failed to generate signing key".to_string(),
+                });
+            }
+        };
+        self.import_key(id, passphrase, algorithm, keypair, lifetime)
+            .await
+    }
+
+    /// Encrypts and stores an existing keypair for an ID that has none yet. Returns None if
+    /// the ID already has a keypair or the keypair is already stored under another ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `algorithm` - Signature algorithm of the keypair
+    /// * `keypair` - Raw public key and PKCS8 secret key to store
+    /// * `lifetime` - Creation time and lifetime of the keypair
+    pub async fn import_key(
+        &self,
+        id: &str,
+        passphrase: &str,
+        algorithm: SigningAlgorithm,
+        keypair: RawKeypair,
+        lifetime: KeyLifetime,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        if self.get_signature_data(id.to_string()).await?.is_some() {
+            return Ok(None);
+        }
+
+        let sig_data = self.create_signature_data(id, passphrase, algorithm, keypair, lifetime);
+        if self.has_pk_hash(&sig_data.pk_hash).await? {
+            return Ok(None);
         }
+
+        self.insert_signature_data(id.to_string(), sig_data.clone())
+            .await?;
+
+        Ok(Some(sig_data))
     }
 
-    /// Signs a message with the private key of the public key hash
+    /// Derives and stores an Ed25519 keypair for an ID that has none yet from the
+    /// master seed, at the given SLIP-0010 path or else at the path of the ID. Returns
+    /// None if the ID already has a keypair or the path is used by another ID
     ///
     /// ### Arguments
     ///
     /// * `id` - ID of the signature entry
     /// * `passphrase` - Passphrase to derive an encryption key from
-    /// * `message` - Message to sign
-    pub async fn sign_message(
+    /// * `path` - Hardened child indices to derive the keypair at
+    /// * `lifetime` - Creation time and lifetime of the keypair
+    pub async fn create_derived_key(
         &self,
         id: &str,
         passphrase: &str,
-        message: Vec<u8>,
-    ) -> Option<(Signature, PublicKey)> {
-        let sig_data = match self.get_signature_data(id.to_string()).await {
-            Ok(sig_data) => sig_data,
-            Err(_) => {
-                let sig_data = self.create_signature_data(id, passphrase);
-
-                match self
-                    .insert_signature_data(id.to_string(), sig_data.clone())
-                    .await
-                {
-                    Ok(_) => sig_data,
-                    Err(e) => {
-                        println!("Error: {:?}", e);
-                        panic!("Failed to insert signature data");
-                    }
-                }
+        path: Option<Vec<u32>>,
+        lifetime: KeyLifetime,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        if self.get_signature_data(id.to_string()).await?.is_some() {
+            return Ok(None);
+        }
+
+        let path = path.unwrap_or_else(|| slip10::id_path(id));
+        let keypair = match self.derive_keypair(passphrase, &path).await? {
+            Some(keypair) => keypair,
+            None => {
+                return Err(DbError {
+                    message: "Failed to derive signing key".to_string(),
+                });
             }
         };
+        let mut sig_data = self.create_signature_data(
+            id,
+            passphrase,
+            SigningAlgorithm::Ed25519,
+            keypair,
+            lifetime,
+        );
+        sig_data.derivation_path = Some(slip10::format_path(&path));
+        if self.has_pk_hash(&sig_data.pk_hash).await? {
+            return Ok(None);
+        }
 
-        println!("Now deriving rest key and continuing");
+        self.insert_signature_data(id.to_string(), sig_data.clone())
+            .await?;
 
-        let rest_key = self.security.derive_rest_key(id, passphrase);
-        if let Some((pub_key, secret_key)) = self
+        Ok(Some(sig_data))
+    }
+
+    /// Derives the Ed25519 keypair at a SLIP-0010 path from the master seed
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `path` - Hardened child indices to derive the keypair at
+    pub async fn derive_keypair(
+        &self,
+        passphrase: &str,
+        path: &[u32],
+    ) -> Result<Option<RawKeypair>, DbError> {
+        let seed = self.master_seed(passphrase).await?;
+        let child_seed = slip10::derive_seed(&seed, path);
+
+        Ok(sign_ed25519::keypair_from_seed(&child_seed)
+            .map(|(public, secret)| (public.as_ref().to_vec(), secret.as_ref().to_vec())))
+    }
+
+    /// Gets the decrypted master seed that keypairs are derived from, generating and
+    /// storing one on first use
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    pub async fn master_seed(&self, passphrase: &str) -> Result<Vec<u8>, DbError> {
+        let seeds = self.open_collection(MASTER_SEED_COLLECTION)?;
+        let rest_key = self.security.derive_rest_key(MASTER_SEED_ID, passphrase);
+
+        // Only store a new seed if there is none, so concurrent first uses agree
+        let encrypted = self
             .security
-            .decrypt_keys_from_storage(rest_key, (sig_data.pub_key, sig_data.secret_key))
-        {
-            let signature = crate::crypto::sign_ed25519::sign_detached(&message, &secret_key);
+            .encrypt_seed_for_storage(rest_key, &generate_random::<32>());
+        let stored = match seeds.compare_and_swap(
+            MASTER_SEED_ID,
+            None as Option<&[u8]>,
+            Some(encrypted.clone()),
+        ) {
+            Ok(Ok(())) => encrypted,
+            Ok(Err(current)) => current
+                .current
+                .map(|seed| seed.to_vec())
+                .unwrap_or_default(),
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to get master seed".to_string(),
+                });
+            }
+        };
 
-            return Some((signature, pub_key));
+        match self.security.decrypt_seed_from_storage(rest_key, stored) {
+            Some(seed) => Ok(seed),
+            None => Err(DbError {
+                message: "Failed to decrypt master seed".to_string(),
+            }),
         }
+    }
+
+    /// Stores the master seed that keypairs are derived from, as when restoring a
+    /// backup. Returns false if a seed is already stored
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `seed` - Master seed to store
+    pub async fn import_master_seed(&self, passphrase: &str, seed: &[u8]) -> Result<bool, DbError> {
+        let seeds = self.open_collection(MASTER_SEED_COLLECTION)?;
+        let rest_key = self.security.derive_rest_key(MASTER_SEED_ID, passphrase);
+        let encrypted = self.security.encrypt_seed_for_storage(rest_key, seed);
 
-        None
+        match seeds.compare_and_swap(MASTER_SEED_ID, None as Option<&[u8]>, Some(encrypted)) {
+            Ok(swapped) => Ok(swapped.is_ok()),
+            Err(_) => Err(DbError {
+                message: "Failed to store master seed".to_string(),
+            }),
+        }
     }
 
-    /// Verifies a message with the signature
+    /// Adds a freshly generated keypair as the newest version of an existing ID, using
+    /// the same algorithm, RSA key size and TTL as the current version. An expiring
+    /// keypair gets a new `not_after` a TTL from now. Returns None if the ID has no
+    /// keypair
     ///
     /// ### Arguments
     ///
     /// * `id` - ID of the signature entry
     /// * `passphrase` - Passphrase to derive an encryption key from
-    /// * `message` - Message to verify
-    /// * `signature` - Signature to verify with
-    pub async fn verify_message(
+    /// * `now` - Current UNIX time
+    pub async fn rotate_key(
         &self,
         id: &str,
         passphrase: &str,
-        message: Vec<u8>,
-        signature: Signature,
-    ) -> bool {
-        let sig_data = self.get_signature_data(id.to_string()).await.unwrap();
-        let rest_key = self.security.derive_rest_key(id, passphrase);
+        now: u64,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        let current = match self.get_key_versions(id.to_string()).await? {
+            Some(versions) => versions.into_iter().max_by_key(|v| v.version),
+            None => None,
+        };
+        let current = match current {
+            Some(current) => current,
+            None => return Ok(None),
+        };
 
-        if let Some((pub_key, _)) = self
-            .security
-            .decrypt_keys_from_storage(rest_key, (sig_data.pub_key, sig_data.secret_key))
-        {
-            return crate::crypto::sign_ed25519::verify_detached(&signature, &message, &pub_key);
+        let rsa_bits = match current.algorithm.rsa_padding() {
+            Some(_) => self
+                .get_public_key(&current, passphrase)
+                .and_then(|pub_key| sign_rsa::PublicKey::from_slice(&pub_key))
+                .and_then(|pub_key| sign_rsa::modulus_bits(&pub_key))
+                .unwrap_or(RSA_DEFAULT_KEY_BITS),
+            None => RSA_DEFAULT_KEY_BITS,
+        };
+        // Derived keypairs move on to the next index of their derivation path
+        let path = match &current.derivation_path {
+            Some(path) => match slip10::parse_path(path).and_then(|p| slip10::next_path(&p)) {
+                Some(path) => Some(path),
+                None => {
+                    return Err(DbError {
+                        message: "Failed to find the next derivation path".to_string(),
+                    });
+                }
+            },
+            None => None,
+        };
+        let keypair = match &path {
+            Some(path) => self.derive_keypair(passphrase, path).await?,
+            None => signing::gen_keypair(current.algorithm, rsa_bits),
+        };
+        let keypair = match keypair {
+            Some(keypair) => keypair,
+            None => {
+                return Err(DbError {
+                    message: "Failed to generate signing key".to_string(),
+                });
+            }
+        };
+        let lifetime = match current.not_after {
+            Some(_) => KeyLifetime::new(now, current.ttl, None),
+            None => KeyLifetime {
+                ttl: current.ttl,
+                ..KeyLifetime::unlimited(now)
+            },
+        };
+        let mut sig_data =
+            self.create_signature_data(id, passphrase, current.algorithm, keypair, lifetime);
+        sig_data.version = current.version + 1;
+        sig_data.disabled = current.disabled;
+        sig_data.disabled_at = current.disabled_at;
+        sig_data.derivation_path = path.map(|path| slip10::format_path(&path));
+
+        self.insert_signature_data(id.to_string(), sig_data.clone())
+            .await?;
+
+        Ok(Some(sig_data))
+    }
+
+    /// Retires an older version of a keypair, so that it no longer verifies. The
+    /// newest version cannot be retired. Returns None if the version does not exist
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `version` - Version to retire
+    /// * `now` - Current UNIX time
+    pub async fn retire_key_version(
+        &self,
+        id: &str,
+        version: u32,
+        now: u64,
+    ) -> Result<Option<bool>, DbError> {
+        let versions = match self.get_key_versions(id.to_string()).await? {
+            Some(versions) => versions,
+            None => return Ok(None),
+        };
+
+        let newest = versions.iter().map(|v| v.version).max();
+        let mut sig_data = match versions.into_iter().find(|v| v.version == version) {
+            Some(sig_data) => sig_data,
+            None => return Ok(None),
+        };
+        if newest == Some(version) {
+            return Ok(Some(false));
+        }
+
+        if sig_data.retired_at.is_none() {
+            sig_data.retired_at = Some(now);
+        }
+        self.insert_signature_data(id.to_string(), sig_data).await?;
+
+        Ok(Some(true))
+    }
+
+    /// Gets the signature data of a single keypair version by its public key hash
+    ///
+    /// ### Arguments
+    ///
+    /// * `pk_hash` - Public key hash
+    pub async fn get_signature_data_by_pk_hash(
+        &self,
+        pk_hash: &str,
+    ) -> Result<Option<SignatureEntry>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        // SigIds share the tree, so anything that is not a full entry is no match
+        let mut sig_data = match db.get(pk_hash) {
+            Ok(Some(sig_data)) => match serde_json::from_slice::<SignatureEntry>(&sig_data) {
+                Ok(sig_data) => sig_data,
+                Err(_) => return Ok(None),
+            },
+            Ok(None) => return Ok(None),
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to get value from database".to_string(),
+                });
+            }
+        };
+        drop(db);
+
+        if sig_data.id.is_empty() {
+            sig_data = match self
+                .list_signature_data()
+                .await?
+                .into_iter()
+                .find(|entry| entry.pk_hash == pk_hash)
+            {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+        }
+
+        Ok(Some(sig_data))
+    }
+
+    /// Checks whether a keypair with the given public key hash is already stored
+    ///
+    /// ### Arguments
+    ///
+    /// * `pk_hash` - Public key hash
+    pub async fn has_pk_hash(&self, pk_hash: &str) -> Result<bool, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db.contains_key(pk_hash) {
+            Ok(exists) => Ok(exists),
+            Err(_) => Err(DbError {
+                message: "Failed to get value from database".to_string(),
+            }),
         }
+    }
 
-        false
+    /// Hands out the next number of a named, persistent serial counter, starting at 1
+    ///
+    /// ### Arguments
+    ///
+    /// * `counter` - Name of the counter
+    pub async fn next_serial(&self, counter: &str) -> Result<u64, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let serials = match db.open_tree(SERIAL_COLLECTION) {
+            Ok(tree) => tree,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open serial collection".to_string(),
+                });
+            }
+        };
+
+        let serial = serials.update_and_fetch(counter, |serial| {
+            let serial = serial
+                .and_then(|serial| serial.try_into().ok())
+                .map_or(0, u64::from_be_bytes);
+            Some((serial + 1).to_be_bytes().to_vec())
+        });
+
+        match serial {
+            Ok(Some(serial)) => Ok(u64::from_be_bytes(serial.as_ref().try_into().unwrap())),
+            _ => Err(DbError {
+                message: "Failed to generate serial number".to_string(),
+            }),
+        }
+    }
+
+    /// Makes sure a signing keypair exists for an ID, creating one only when
+    /// the ID is definitely not present. Used for the server's own keys
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `now` - Current UNIX time
+    pub async fn ensure_key(&self, id: &str, passphrase: &str, now: u64) -> Result<(), DbError> {
+        self.create_key(
+            id,
+            passphrase,
+            SigningAlgorithm::default(),
+            RSA_DEFAULT_KEY_BITS,
+            KeyLifetime::unlimited(now),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Gets the decrypted raw public key of a signature entry
+    ///
+    /// ### Arguments
+    ///
+    /// * `sig_data` - Signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    pub fn get_public_key(&self, sig_data: &SignatureEntry, passphrase: &str) -> Option<Vec<u8>> {
+        let rest_key = self.security.derive_rest_key(&sig_data.id, passphrase);
+        self.security
+            .decrypt_keys_from_storage(
+                rest_key,
+                (sig_data.pub_key.clone(), sig_data.secret_key.clone()),
+            )
+            .map(|(pub_key, _)| pub_key)
+    }
+
+    /// Disables a signing keypair so that it can no longer be used to sign.
+    /// Returns false if no keypair exists for the ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `now` - Current UNIX time
+    pub async fn disable_key(&self, id: &str, now: u64) -> Result<bool, DbError> {
+        let versions = match self.get_key_versions(id.to_string()).await? {
+            Some(versions) => versions,
+            None => return Ok(false),
+        };
+
+        for mut sig_data in versions.into_iter().filter(|v| !v.disabled) {
+            sig_data.disabled = true;
+            sig_data.disabled_at = Some(now);
+            self.insert_signature_data(id.to_string(), sig_data).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Lists every stored signing keypair
+    pub async fn list_signature_data(&self) -> Result<Vec<SignatureEntry>, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        // SigIds and full entries share the tree, so tell them apart by shape
+        let mut sig_ids = HashMap::new();
+        let mut entries = Vec::new();
+        for value in db.iter().values() {
+            let value = match value {
+                Ok(value) => value,
+                Err(_) => {
+                    return Err(DbError {
+                        message: "Failed to read signature data".to_string(),
+                    });
+                }
+            };
+
+            if let Ok(sig_data) = serde_json::from_slice::<SignatureEntry>(&value) {
+                entries.push(sig_data);
+            } else if let Ok(sig_id) = serde_json::from_slice::<SigId>(&value) {
+                sig_ids.insert(sig_id.pk_hash, sig_id.id);
+            }
+        }
+
+        for sig_data in entries.iter_mut().filter(|e| e.id.is_empty()) {
+            if let Some(id) = sig_ids.get(&sig_data.pk_hash) {
+                sig_data.id = id.clone();
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Deletes a signing keypair. Returns false if no keypair exists for the ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    pub async fn delete_key(&self, id: &str) -> Result<bool, DbError> {
+        let versions = match self.get_key_versions(id.to_string()).await? {
+            Some(versions) => versions,
+            None => return Ok(false),
+        };
+
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        let removed = versions
+            .iter()
+            .try_for_each(|sig_data| db.remove(&sig_data.pk_hash).map(|_| ()))
+            .and_then(|_| db.remove(id))
+            .and_then(|_| db.flush());
+        self.invalidate_jwks();
+
+        match removed {
+            Ok(_) => Ok(true),
+            Err(_) => Err(DbError {
+                message: "Failed to remove signature data".to_string(),
+            }),
+        }
+    }
+
+    /// Deletes every keypair whose versions have all expired beyond the grace period,
+    /// and returns their IDs
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current UNIX time
+    pub async fn purge_expired_keys(&self, now: u64) -> Result<Vec<String>, DbError> {
+        // The newest version cannot be retired, so a keypair none of whose versions
+        // verify any more has expired
+        let mut ids: HashMap<String, bool> = HashMap::new();
+        for sig_data in self.list_signature_data().await? {
+            let all_expired = ids.entry(sig_data.id.clone()).or_insert(true);
+            *all_expired &= !sig_data.verifies_at(now);
+        }
+
+        let mut purged = Vec::new();
+        for (id, all_expired) in ids {
+            if all_expired && self.delete_key(&id).await? {
+                purged.push(id);
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Stores a verification policy. Returns false if a policy with the name exists
+    ///
+    /// ### Arguments
+    ///
+    /// * `policy` - Policy to store
+    pub async fn create_policy(&self, policy: &VerificationPolicy) -> Result<bool, DbError> {
+        let policies = self.open_collection(POLICY_COLLECTION)?;
+        let policy_json = serde_json::to_vec(policy).unwrap();
+
+        match policies.compare_and_swap(&policy.name, None as Option<&[u8]>, Some(policy_json)) {
+            Ok(swapped) => Ok(swapped.is_ok()),
+            Err(_) => Err(DbError {
+                message: "Failed to store policy".to_string(),
+            }),
+        }
+    }
+
+    /// Gets a verification policy by name
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the policy
+    pub async fn get_policy(&self, name: &str) -> Result<Option<VerificationPolicy>, DbError> {
+        let policies = self.open_collection(POLICY_COLLECTION)?;
+
+        match policies.get(name) {
+            Ok(policy) => Ok(policy.map(|policy| serde_json::from_slice(&policy).unwrap())),
+            Err(_) => Err(DbError {
+                message: "Failed to get policy".to_string(),
+            }),
+        }
+    }
+
+    /// Deletes a verification policy. Returns false if no policy has the name
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the policy
+    pub async fn delete_policy(&self, name: &str) -> Result<bool, DbError> {
+        let policies = self.open_collection(POLICY_COLLECTION)?;
+
+        match policies.remove(name) {
+            Ok(removed) => Ok(removed.is_some()),
+            Err(_) => Err(DbError {
+                message: "Failed to delete policy".to_string(),
+            }),
+        }
+    }
+
+    /// Checks signatures over a message against a verification policy. A signature
+    /// names the signer it is from, or counts for the first signer whose key it
+    /// verifies under that has no valid signature yet. Each signature counts for at
+    /// most one signer and repeated signatures are ignored. Stored keypairs of
+    /// signers accept every version that still verifies
+    ///
+    /// ### Arguments
+    ///
+    /// * `policy` - Policy to check against
+    /// * `passphrase` - Passphrase to derive encryption keys from
+    /// * `message` - Signed message
+    /// * `signatures` - Signer name, if given, and signature of each signature
+    /// * `now` - Current UNIX time
+    pub async fn verify_policy(
+        &self,
+        policy: &VerificationPolicy,
+        passphrase: &str,
+        message: &[u8],
+        signatures: Vec<(Option<String>, Vec<u8>)>,
+        now: u64,
+    ) -> Result<PolicyVerification, DbError> {
+        let mut signer_keys = Vec::with_capacity(policy.signers.len());
+        for signer in &policy.signers {
+            let keys = match (&signer.public_key, &signer.key_id) {
+                (Some(public_key), _) => hex::decode(public_key).into_iter().collect(),
+                (None, Some(key_id)) => self
+                    .load_verifying_keys(key_id, passphrase, now)
+                    .await?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|(algorithm, _)| *algorithm == SigningAlgorithm::Ed25519)
+                    .map(|(_, pub_key)| pub_key)
+                    .collect(),
+                (None, None) => Vec::new(),
+            };
+            signer_keys.push(keys);
+        }
+
+        let mut verification = PolicyVerification::default();
+        let mut seen_signatures: Vec<Vec<u8>> = Vec::with_capacity(signatures.len());
+        for (name, signature) in signatures {
+            if seen_signatures.contains(&signature) {
+                continue;
+            }
+            seen_signatures.push(signature.clone());
+
+            let signature = sign_ed25519::Signature::from_slice(&signature);
+            let mut matched = false;
+            let mut counted = false;
+            for (signer, keys) in policy.signers.iter().zip(&signer_keys) {
+                if name.as_ref().is_some_and(|name| *name != signer.name) {
+                    continue;
+                }
+
+                let valid = signature.is_some_and(|signature| {
+                    keys.iter()
+                        .filter_map(|key| sign_ed25519::PublicKey::from_slice(key))
+                        .any(|key| sign_ed25519::verify_detached(&signature, message, &key))
+                });
+                if valid && !counted && !verification.valid_signers.contains(&signer.name) {
+                    verification.valid_signers.push(signer.name.clone());
+                    counted = true;
+                }
+                matched |= valid;
+            }
+
+            match name {
+                Some(name) if !policy.signers.iter().any(|s| s.name == name) => {
+                    verification.unknown_signers.push(name)
+                }
+                Some(name) if !matched => verification.invalid_signers.push(name),
+                None if !matched => verification.invalid_signatures += 1,
+                _ => {}
+            }
+        }
+        verification.satisfied = verification.valid_signers.len() >= policy.threshold;
+
+        Ok(verification)
+    }
+
+    /// Opens a collection of the signature database
+    ///
+    /// ### Arguments
+    ///
+    /// * `collection` - Name of the collection
+    fn open_collection(&self, collection: &str) -> Result<sled::Tree, DbError> {
+        let db = match open_db(&self.url) {
+            Ok(db) => db,
+            Err(_) => {
+                return Err(DbError {
+                    message: "Failed to open database".to_string(),
+                });
+            }
+        };
+
+        match db.open_tree(collection) {
+            Ok(tree) => Ok(tree),
+            Err(_) => Err(DbError {
+                message: format!("Failed to open {} collection", collection),
+            }),
+        }
+    }
+
+    /// Creates a signature entry
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `algorithm` - Signature algorithm of the keypair
+    /// * `keypair` - Raw public key and PKCS8 secret key to encrypt for storage
+    /// * `lifetime` - Creation time and lifetime of the keypair
+    pub fn create_signature_data(
+        &self,
+        id: &str,
+        passphrase: &str,
+        algorithm: SigningAlgorithm,
+        keypair: RawKeypair,
+        lifetime: KeyLifetime,
+    ) -> SignatureEntry {
+        let pk_hash = hex::encode(sha3_256::digest(&keypair.0));
+        let rest_key = self.security.derive_rest_key(id, passphrase);
+        let (pub_key, secret_key) = self.security.encrypt_keys_for_storage(rest_key, keypair);
+
+        SignatureEntry {
+            id: id.to_string(),
+            pk_hash,
+            pub_key,
+            secret_key,
+            ttl: lifetime.ttl,
+            timestamp: lifetime.created_at.to_string(),
+            disabled: false,
+            disabled_at: None,
+            version: first_version(),
+            retired_at: None,
+            algorithm,
+            not_after: lifetime.not_after,
+            derivation_path: None,
+        }
+    }
+
+    /// Signs a message with the newest version of an existing, enabled keypair.
+    /// Returns None if no such keypair exists for the ID or it has expired
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `message` - Message to sign
+    /// * `encoding` - Encoding of the signature, for ECDSA keypairs
+    /// * `now` - Current UNIX time
+    pub async fn sign_message(
+        &self,
+        id: &str,
+        passphrase: &str,
+        message: Vec<u8>,
+        encoding: SignatureEncoding,
+        now: u64,
+    ) -> Result<Option<SignedMessage>, DbError> {
+        match self.load_signing_key(id, passphrase, now).await? {
+            Some(key) => key.sign(&message, encoding).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Signs a batch of messages, loading and decrypting the keypair of each distinct
+    /// ID only once. Every message gets its own result, in the same order
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase to derive encryption keys from
+    /// * `messages` - ID, message and signature encoding of each message to sign
+    /// * `now` - Current UNIX time
+    pub async fn sign_messages(
+        &self,
+        passphrase: &str,
+        messages: Vec<(String, Vec<u8>, SignatureEncoding)>,
+        now: u64,
+    ) -> Vec<Result<Option<SignedMessage>, DbError>> {
+        let mut keys = HashMap::new();
+        let mut results = Vec::with_capacity(messages.len());
+
+        for (id, message, encoding) in messages {
+            if !keys.contains_key(&id) {
+                let key = self.load_signing_key(&id, passphrase, now).await;
+                keys.insert(id.clone(), key);
+            }
+
+            results.push(match &keys[&id] {
+                Ok(Some(key)) => key.sign(&message, encoding).map(Some),
+                Ok(None) => Ok(None),
+                Err(e) => Err(e.clone()),
+            });
+        }
+
+        results
+    }
+
+    /// Verifies a message with the signature, accepting any version of the ID's keypair
+    /// that has not been retired or expired beyond its grace period. Returns None if no
+    /// keypair exists for the ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `message` - Message to verify
+    /// * `signature` - Signature to verify with
+    /// * `now` - Current UNIX time
+    pub async fn verify_message(
+        &self,
+        id: &str,
+        passphrase: &str,
+        message: Vec<u8>,
+        signature: &[u8],
+        now: u64,
+    ) -> Result<Option<bool>, DbError> {
+        Ok(self
+            .load_verifying_keys(id, passphrase, now)
+            .await?
+            .map(|keys| verify_with_any(&keys, &message, signature)))
+    }
+
+    /// Verifies a batch of signatures, loading and decrypting the public keys of each
+    /// distinct ID only once. Every signature gets its own result, in the same order
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase to derive encryption keys from
+    /// * `messages` - ID, message and signature of each message to verify
+    /// * `now` - Current UNIX time
+    pub async fn verify_messages(
+        &self,
+        passphrase: &str,
+        messages: Vec<(String, Vec<u8>, Vec<u8>)>,
+        now: u64,
+    ) -> Vec<Result<Option<bool>, DbError>> {
+        let mut keys = HashMap::new();
+        let mut results = Vec::with_capacity(messages.len());
+
+        for (id, message, signature) in messages {
+            if !keys.contains_key(&id) {
+                let versions = self.load_verifying_keys(&id, passphrase, now).await;
+                keys.insert(id.clone(), versions);
+            }
+
+            results.push(match &keys[&id] {
+                Ok(Some(versions)) => Ok(Some(verify_with_any(versions, &message, &signature))),
+                Ok(None) => Ok(None),
+                Err(e) => Err(e.clone()),
+            });
+        }
+
+        results
+    }
+
+    /// Signs a message with the newest version of an existing, enabled Ed25519
+    /// keypair, returning the message with the signature appended and the key
+    /// version. Returns None if no such keypair exists for the ID or it has expired
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `message` - Message to sign
+    /// * `now` - Current UNIX time
+    pub async fn sign_message_attached(
+        &self,
+        id: &str,
+        passphrase: &str,
+        message: Vec<u8>,
+        now: u64,
+    ) -> Result<Option<(Vec<u8>, u32)>, DbError> {
+        let key = match self.load_signing_key(id, passphrase, now).await? {
+            Some(key) if key.algorithm == SigningAlgorithm::Ed25519 => key,
+            _ => return Ok(None),
+        };
+
+        match sign_ed25519::SecretKey::from_slice(&key.secret_key) {
+            Some(secret_key) => Ok(Some((
+                sign_ed25519::sign_append(&message, &secret_key),
+                key.version,
+            ))),
+            None => Err(DbError {
+                message: "Failed to sign message".to_string(),
+            }),
+        }
+    }
+
+    /// Verifies a message with its signature appended, accepting any Ed25519 version
+    /// of the ID's keypair that still verifies. Returns None if no keypair exists for
+    /// the ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `signed_message` - Message followed by its signature
+    /// * `now` - Current UNIX time
+    pub async fn verify_message_attached(
+        &self,
+        id: &str,
+        passphrase: &str,
+        signed_message: &[u8],
+        now: u64,
+    ) -> Result<Option<bool>, DbError> {
+        let keys = match self.load_verifying_keys(id, passphrase, now).await? {
+            Some(keys) => keys,
+            None => return Ok(None),
+        };
+
+        Ok(Some(keys.iter().any(|(algorithm, pub_key)| {
+            *algorithm == SigningAlgorithm::Ed25519
+                && sign_ed25519::PublicKey::from_slice(pub_key)
+                    .is_some_and(|pub_key| sign_ed25519::verify_append(signed_message, &pub_key))
+        })))
+    }
+
+    /// Loads and decrypts the newest version of an existing, enabled keypair.
+    /// Returns None if no such keypair exists for the ID or it has expired
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `now` - Current UNIX time
+    async fn load_signing_key(
+        &self,
+        id: &str,
+        passphrase: &str,
+        now: u64,
+    ) -> Result<Option<SigningKey>, DbError> {
+        let sig_data = match self.get_signature_data(id.to_string()).await? {
+            Some(sig_data) if !sig_data.disabled && !sig_data.is_expired(now) => sig_data,
+            _ => return Ok(None),
+        };
+
+        println!("Now deriving rest key and continuing");
+
+        let rest_key = self.security.derive_rest_key(id, passphrase);
+        match self
+            .security
+            .decrypt_keys_from_storage(rest_key, (sig_data.pub_key, sig_data.secret_key))
+        {
+            Some((public_key, secret_key)) => Ok(Some(SigningKey {
+                public_key,
+                secret_key,
+                version: sig_data.version,
+                algorithm: sig_data.algorithm,
+            })),
+            None => Err(DbError {
+                message: "Failed to decrypt signing key".to_string(),
+            }),
+        }
+    }
+
+    /// Loads and decrypts the public keys of every version of the ID's keypair that
+    /// still verifies. Returns None if no keypair exists for the ID
+    ///
+    /// ### Arguments
+    ///
+    /// * `id` - ID of the signature entry
+    /// * `passphrase` - Passphrase to derive an encryption key from
+    /// * `now` - Current UNIX time
+    async fn load_verifying_keys(
+        &self,
+        id: &str,
+        passphrase: &str,
+        now: u64,
+    ) -> Result<Option<Vec<VerifyingKey>>, DbError> {
+        let versions = match self.get_key_versions(id.to_string()).await? {
+            Some(versions) => versions,
+            None => return Ok(None),
+        };
+
+        let rest_key = self.security.derive_rest_key(id, passphrase);
+        let mut keys = Vec::with_capacity(versions.len());
+        for sig_data in versions.into_iter().filter(|v| v.verifies_at(now)) {
+            match self
+                .security
+                .decrypt_keys_from_storage(rest_key, (sig_data.pub_key, sig_data.secret_key))
+            {
+                Some((pub_key, _)) => keys.push((sig_data.algorithm, pub_key)),
+                None => {
+                    return Err(DbError {
+                        message: "Failed to decrypt public key".to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(Some(keys))
+    }
+}
+
+/// A decrypted keypair, ready to sign with
+struct SigningKey {
+    public_key: Vec<u8>,
+    secret_key: Vec<u8>,
+    version: u32,
+    algorithm: SigningAlgorithm,
+}
+
+impl SigningKey {
+    /// Signs a message with the keypair
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Message to sign
+    /// * `encoding` - Encoding of the signature, for ECDSA keypairs
+    fn sign(&self, message: &[u8], encoding: SignatureEncoding) -> Result<SignedMessage, DbError> {
+        match signing::sign(self.algorithm, encoding, message, &self.secret_key) {
+            Some(signature) => Ok(SignedMessage {
+                signature,
+                public_key: self.public_key.clone(),
+                version: self.version,
+                algorithm: self.algorithm,
+            }),
+            None => Err(DbError {
+                message: "Failed to sign message".to_string(),
+            }),
+        }
+    }
+}
+
+/// Algorithm and raw public key of one version of a keypair
+type VerifyingKey = (SigningAlgorithm, Vec<u8>);
+
+/// Checks a signature against every given public key
+///
+/// ### Arguments
+///
+/// * `keys` - Public keys to try
+/// * `message` - Signed message
+/// * `signature` - Signature to verify
+fn verify_with_any(keys: &[VerifyingKey], message: &[u8], signature: &[u8]) -> bool {
+    keys.iter()
+        .any(|(algorithm, pub_key)| signing::verify(*algorithm, signature, message, pub_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::secretbox_chacha20_poly1305::NONCE_LEN;
+
+    const PASSPHRASE: &str = "test";
+
+    /// Creates a signature database URL inside an empty test directory
+    fn test_url() -> String {
+        let dir = std::env::temp_dir().join(format!(
+            "freemason-test-{}",
+            hex::encode(generate_random::<8>())
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("signatures").to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn master_seed_opens_in_a_new_instance() {
+        let url = test_url();
+        let seed = SignatureDb::new(url.clone())
+            .master_seed(PASSPHRASE)
+            .await
+            .unwrap();
+
+        // A new instance has its own security at rest, as after a restart
+        let reopened = SignatureDb::new(url).master_seed(PASSPHRASE).await;
+        assert_eq!(reopened.unwrap(), seed);
+    }
+
+    #[tokio::test]
+    async fn key_versions_open_in_a_new_instance() {
+        let url = test_url();
+        let sig_db = SignatureDb::new(url.clone());
+        let lifetime = KeyLifetime::unlimited(0);
+        let algorithm = SigningAlgorithm::Ed25519;
+        let first = sig_db
+            .create_key(
+                "rotated",
+                PASSPHRASE,
+                algorithm,
+                RSA_DEFAULT_KEY_BITS,
+                lifetime,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let second = sig_db
+            .rotate_key("rotated", PASSPHRASE, 0)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Every sealed value starts with a nonce of its own
+        let nonces: Vec<_> = [&first.pub_key, &first.secret_key, &second.pub_key]
+            .iter()
+            .map(|sealed| sealed[..NONCE_LEN].to_vec())
+            .collect();
+        assert_ne!(nonces[0], nonces[1]);
+        assert_ne!(nonces[0], nonces[2]);
+
+        let reopened = SignatureDb::new(url);
+        for sig_data in [&first, &second] {
+            let pub_key = reopened.get_public_key(sig_data, PASSPHRASE).unwrap();
+            assert_eq!(hex::encode(sha3_256::digest(&pub_key)), sig_data.pk_hash);
+        }
     }
 }
@@ -3,6 +3,8 @@ pub mod crypto;
 pub mod db;
 
 use crate::api::routes::*;
+use crate::api::tasks::{key_expiry_sweeper, retention_sweeper};
+use crate::db::log_db::LogDb;
 use crate::db::secret_db::SecretDb;
 use crate::db::sign_db::SignatureDb;
 use futures::lock::Mutex;
@@ -11,14 +13,102 @@ use warp::Filter;
 
 #[tokio::main]
 async fn main() {
+    env_logger::init();
+
     let passphrase: String = "test".to_string();
     let sig_db = Arc::new(Mutex::new(SignatureDb::new("db/signatures".to_string())));
     let sec_db = Arc::new(Mutex::new(SecretDb::new("db/secret".to_string())));
+    let log_db = Arc::new(Mutex::new(LogDb::new("db/transparency_log".to_string())));
+
+    let file_routes = upload_raw(sec_db.clone(), passphrase.clone())
+        .or(download(sec_db.clone(), passphrase.clone()))
+        .or(share_create(
+            sec_db.clone(),
+            sig_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(share_download(
+            sec_db.clone(),
+            sig_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(burn_create(sec_db.clone(), passphrase.clone()))
+        .or(burn_read(sec_db.clone(), passphrase.clone()))
+        .or(legal_hold(sec_db.clone()))
+        .or(delete_file(sec_db.clone()))
+        .or(file_sign(
+            sec_db.clone(),
+            sig_db.clone(),
+            log_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(file_signature(sec_db.clone()))
+        .or(file_verify(
+            sec_db.clone(),
+            sig_db.clone(),
+            passphrase.clone(),
+        ));
+
+    let key_routes = key_create(sig_db.clone(), passphrase.clone())
+        .or(master_seed_import(sig_db.clone(), passphrase.clone()))
+        .or(key_import(sig_db.clone(), passphrase.clone()))
+        .or(key_derivation(sig_db.clone(), passphrase.clone()))
+        .or(key_rotate(sig_db.clone(), passphrase.clone()))
+        .or(key_retire(sig_db.clone()))
+        .or(key_get(sig_db.clone(), passphrase.clone()))
+        .or(key_public(sig_db.clone(), passphrase.clone()))
+        .or(jwks(sig_db.clone(), passphrase.clone()))
+        .or(key_delete(sig_db.clone()))
+        .or(key_disable(sig_db.clone()));
+
+    let signing_routes = sign_attached(sig_db.clone(), log_db.clone(), passphrase.clone())
+        .or(verify_attached(sig_db.clone(), passphrase.clone()))
+        .or(sign_sshsig(
+            sig_db.clone(),
+            log_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(verify_sshsig())
+        .or(sign_batch(
+            sig_db.clone(),
+            log_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(verify_batch(sig_db.clone(), passphrase.clone()))
+        .or(sign_raw(sig_db.clone(), log_db.clone(), passphrase.clone()))
+        .or(sign(sig_db.clone(), log_db.clone(), passphrase.clone()))
+        .or(verify_policy(sig_db.clone(), passphrase.clone()))
+        .or(verify(sig_db.clone(), passphrase.clone()))
+        .or(jwt_issue(
+            sig_db.clone(),
+            log_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(jwt_verify(sig_db.clone(), passphrase.clone()))
+        .or(timestamp(
+            sig_db.clone(),
+            log_db.clone(),
+            passphrase.clone(),
+        ))
+        .or(timestamp_verify(sig_db.clone(), passphrase.clone()));
+
+    let policy_routes = policy_create(sig_db.clone())
+        .or(policy_get(sig_db.clone()))
+        .or(policy_delete(sig_db.clone()));
+
+    let log_routes = log_tree_head(log_db.clone(), sig_db.clone(), passphrase)
+        .or(log_entry(log_db.clone()))
+        .or(log_inclusion_proof(log_db.clone()))
+        .or(log_consistency_proof(log_db));
+
+    let routes = file_routes
+        .or(key_routes)
+        .or(signing_routes)
+        .or(policy_routes)
+        .or(log_routes);
 
-    let routes = upload_raw(sec_db.clone(), passphrase.clone())
-        .or(download(sec_db, passphrase.clone()))
-        .or(sign(sig_db.clone(), passphrase.clone()))
-        .or(verify(sig_db, passphrase));
+    tokio::spawn(retention_sweeper(sec_db));
+    tokio::spawn(key_expiry_sweeper(sig_db));
 
     println!("Server running on port 3030");
     warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;